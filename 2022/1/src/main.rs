@@ -1,9 +1,8 @@
+use lattice::args::arg;
+use lattice::{Beta, Boundary, ExternalField, LatticeModel, MultispinIsing, Palette, Recorder, Sampler, Schedule, Shape};
 use nannou::image;
 use nannou::prelude::*;
-use ndarray::prelude::*;
-use ndarray::{Array, Ix2};
-use rand::prelude::*;
-use rand_pcg::Pcg64;
+use std::path::PathBuf;
 
 const SEED: u64 = 12345;
 const WINDOW_X: u32 = 1000;
@@ -14,46 +13,99 @@ const BETA_C: f32 = 0.440686793509772; // (2.).sqrt().ln_1p() / 2.;
 const BETA_START: f32 = 0.5 * BETA_C;
 const BETA_END: f32 = 1.5 * BETA_C;
 const N_STEPS: u32 = 100;
+const SHAPE: Shape = Shape::Linear;
+const SAMPLER: Sampler = Sampler::Metropolis;
+const BOUNDARY: Boundary = Boundary::Periodic;
+const IMAGE: &str = "../25/assets/nature_1.jpg";
+const FIELD_H: f32 = 0.0;
+const MULTISPIN: bool = false;
+const PALETTE: &str = "mono";
 
 struct Model {
     _window: window::Id,
     w_x: u32,
     w_y: u32,
-    n_x: usize,
-    n_y: usize,
     down_rgba: [u8; 4],
     up_rgba: [u8; 4],
-    a: Array<i8, Ix2>,
-    rng: rand_pcg::Pcg64,
-    beta: f32,
-    beta_delta: f32,
+    engine: Engine,
+    recorder: Recorder,
+    schedule: Schedule,
+    step: usize,
     texture: wgpu::Texture,
 }
 
 fn model(app: &App) -> Model {
     let _window = app
         .new_window()
-        .size(WINDOW_X, WINDOW_Y)
+        .size(arg("width").unwrap_or(WINDOW_X), arg("height").unwrap_or(WINDOW_Y))
         .view(view)
         .build()
         .unwrap();
-    let rng = Pcg64::seed_from_u64(SEED);
-    let beta: f32 = BETA_START;
-    let beta_delta: f32 = (BETA_END - BETA_START) / N_STEPS as f32;
+    // a loop out to the far end and back, or keyframes from --schedule path
+    let schedule: Schedule = match arg::<PathBuf>("schedule") {
+        Some(path) => Schedule::load(path).unwrap(),
+        None => Schedule::cycle(arg("shape").unwrap_or(SHAPE), BETA_START, BETA_END, 2 * N_STEPS as usize),
+    };
+    println!("schedule {}", schedule);
+    let beta = schedule.at(0);
     let window = app.main_window();
     let wh = window.rect();
-    let w_x: u32 = SPIN_WIDTH_X;
-    let w_y: u32 = SPIN_WIDTH_Y;
-    let n_x: usize = wh.w() as usize / w_x as usize;
-    let n_y: usize = wh.h() as usize / w_y as usize;
-
-    let down_rgba: [u8; 4] = [0, 0, 0, u8::MAX];
-    let up_rgba: [u8; 4] = [u8::MAX, u8::MAX, u8::MAX, u8::MAX];
-
-    println!("w_x {}, w_y {}, n_x {}, n_y {}", w_x, w_y, n_x, n_y);
-    let a = Array::<i8, Ix2>::ones((n_x, n_y).f());
+    let w_x: u32 = arg("spin-width").unwrap_or(SPIN_WIDTH_X);
+    let w_y: u32 = arg("spin-width").unwrap_or(SPIN_WIDTH_Y);
+    let mut n_x: usize = wh.w() as usize / w_x as usize;
+    let mut n_y: usize = wh.h() as usize / w_y as usize;
+
+    // two spin states, so two colours: down then up
+    let palette: Palette = arg("palette").unwrap_or_else(|| PALETTE.parse().unwrap());
+    assert_eq!(palette.len(), 2, "an Ising palette needs two colours, not {}", palette);
+    println!("palette {}", palette);
+    let [down_rgba, up_rgba] = [palette.colours[0], palette.colours[1]];
+
+    // packed Ising spins, 64 to a word, for lattices of a spin per pixel
+    let multispin: bool = arg("multispin").unwrap_or(MULTISPIN);
+    let engine = if multispin {
+        // the checkerboard needs even sides
+        n_x -= n_x % 2;
+        n_y -= n_y % 2;
+        println!("multispin, w_x {}, w_y {}, n_x {}, n_y {}", w_x, w_y, n_x, n_y);
+        let mut lattice = MultispinIsing::new(n_x, n_y, beta, SEED);
+        lattice.fill(1);
+        lattice.n_threads = arg("threads").unwrap_or(lattice.n_threads);
+        lattice.j = arg("j").unwrap_or(lattice.j);
+        println!("threads {}, j {}", lattice.n_threads, lattice.j);
+        Engine::Multispin(lattice)
+    } else {
+        println!("w_x {}, w_y {}, n_x {}, n_y {}", w_x, w_y, n_x, n_y);
+        let mut lattice = LatticeModel::new(n_x, n_y, 2, Beta::Global(beta), SEED);
+        lattice.a.fill(1);
+        lattice.sampler = arg("sampler").unwrap_or(SAMPLER);
+        lattice.set_boundary(arg("boundary").unwrap_or(BOUNDARY));
+        lattice.couplings = arg("couplings").unwrap_or(lattice.couplings);
+        println!("sampler {}, boundary {}, couplings {}", lattice.sampler, lattice.boundary, lattice.couplings);
+
+        // pull the spins toward a picture, light pixels up and dark pixels down
+        let field_h: f32 = arg("field").unwrap_or(FIELD_H);
+        if field_h != 0.0 {
+            let image_path: String = arg("image").unwrap_or_else(|| IMAGE.to_string());
+            let picture = image::open(app.project_path().unwrap().join(&image_path)).unwrap().to_rgba8();
+            let (width, height) = picture.dimensions();
+            lattice.field = Some(ExternalField::from_luminance(
+                width as usize,
+                height as usize,
+                picture.as_raw(),
+                n_x,
+                n_y,
+                2,
+                field_h,
+            ));
+            println!("image {}, field {}", image_path, field_h);
+        }
+        Engine::Potts(Box::new(lattice))
+    };
+    let recorder = Recorder::create(observables_path(app)).unwrap();
+    let step: usize = 0;
     let texture = wgpu::TextureBuilder::new()
-        .size([wh.w() as u32, wh.h() as u32])
+        .size([n_x as u32 * w_x, n_y as u32 * w_y])
         .format(wgpu::TextureFormat::Rgba8Unorm)
         .usage(wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING)
         .build(window.device());
@@ -62,54 +114,52 @@ fn model(app: &App) -> Model {
         _window,
         w_x,
         w_y,
-        n_x,
-        n_y,
-        a,
         down_rgba,
         up_rgba,
-        rng,
-        beta,
-        beta_delta,
+        engine,
+        recorder,
+        schedule,
+        step,
         texture,
     }
 }
 
+/// The lattice a run steps: the general Potts lattice, or packed Ising spins.
+enum Engine {
+    Potts(Box<LatticeModel>),
+    Multispin(MultispinIsing),
+}
+
 fn update(_app: &App, _model: &mut Model, _update: Update) {
-    let n_x = _model.n_x;
-    let n_y = _model.n_y;
-    let beta = _model.beta;
-    for _ in 0..(n_x * n_y) {
-        let i: usize = _model.rng.gen_range(0..n_x);
-        let j: usize = _model.rng.gen_range(0..n_y);
-
-        // compute energy of current state and new state
-        let old_energy = -_model.a[[i, j]]
-            * (_model.a[[(i + n_x - 1) % n_x, j]]
-                + _model.a[[(i + 1) % n_x, j]]
-                + _model.a[[i, (j + n_y - 1) % n_y]]
-                + _model.a[[i, (j + 1) % n_y]]);
-        let new_energy = -old_energy;
-
-        // flip a coin
-        if _model.rng.gen::<f32>() < (beta * (old_energy - new_energy) as f32).exp() {
-            _model.a[[i, j]] *= -1;
+    match &mut _model.engine {
+        Engine::Potts(lattice) => {
+            lattice.sweep();
+            _model.recorder.record(&**lattice).unwrap();
+        }
+        Engine::Multispin(lattice) => {
+            lattice.sweep();
+            _model.recorder.record(lattice).unwrap();
         }
     }
 
-    // increment beta
-    if _model.beta > BETA_END || _model.beta < BETA_START {
-        _model.beta_delta *= -1.;
+    // next step's beta
+    let beta = _model.schedule.at(_model.step + 1);
+    match &mut _model.engine {
+        Engine::Potts(lattice) => lattice.beta = Beta::Global(beta),
+        Engine::Multispin(lattice) => lattice.beta = beta,
     }
-    _model.beta += _model.beta_delta;
-    println!("beta {}, beta_end {}, beta_start {}, beta_delta {}", _model.beta, BETA_END, BETA_START, _model.beta_delta);
+    println!("beta {}, step {}", beta, _model.step + 1);
+
+    // step
+    _model.step += 1
 }
 
-fn get_rgba(pixel_x: usize, pixel_y: usize, _model: &Model) -> [u8; 4] {
-    let i: usize = (pixel_x / _model.w_x as usize) % _model.n_x;
-    let j: usize = (pixel_y / _model.w_y as usize) % _model.n_y;
+fn get_rgba(pixel_x: usize, pixel_y: usize, lattice: &LatticeModel, _model: &Model) -> [u8; 4] {
+    let i: usize = (pixel_x / _model.w_x as usize) % lattice.n_x;
+    let j: usize = (pixel_y / _model.w_y as usize) % lattice.n_y;
     //println!("pixel_x {}, pixel_y {}, i {}, j {}", pixel_x, pixel_y, i, j);
-    let val = _model.a[[i, j]];
-    if val == -1 {
+    let val = lattice.a[[i, j]];
+    if val == 0 {
         _model.down_rgba
     } else {
         _model.up_rgba
@@ -119,18 +169,23 @@ fn get_rgba(pixel_x: usize, pixel_y: usize, _model: &Model) -> [u8; 4] {
 fn view(app: &App, _model: &Model, frame: Frame) {
     frame.clear(WHITE);
 
-    let wh = app.window_rect().wh();
-    let image = image::ImageBuffer::from_fn(wh.x as u32, wh.y as u32, |i, j| {
-        let rgba = get_rgba(i as usize, j as usize, _model);
-        nannou::image::Rgba(rgba)
-    });
-
-    let flat_samples = image.as_flat_samples();
-    _model.texture.upload_data(
-        app.main_window().device(),
-        &mut *frame.command_encoder(),
-        &flat_samples.as_slice(),
-    );
+    let [width, height] = _model.texture.size();
+    let rgba: Vec<u8> = match &_model.engine {
+        Engine::Potts(lattice) => {
+            let image = image::ImageBuffer::from_fn(width, height, |i, j| {
+                let rgba = get_rgba(i as usize, j as usize, lattice, _model);
+                nannou::image::Rgba(rgba)
+            });
+            image.into_raw()
+        }
+        Engine::Multispin(lattice) => {
+            // straight from the packed words, a block of pixels per spin
+            let mut rgba = vec![0; 4 * width as usize * height as usize];
+            lattice.unpack_rgba(_model.down_rgba, _model.up_rgba, _model.w_x as usize, _model.w_y as usize, &mut rgba);
+            rgba
+        }
+    };
+    _model.texture.upload_data(app.main_window().device(), &mut *frame.command_encoder(), &rgba);
 
     let draw = app.draw();
     draw.texture(&_model.texture);
@@ -153,6 +208,15 @@ fn captured_frame_path(app: &App, frame: &Frame) -> std::path::PathBuf {
         .with_extension("png")
 }
 
+fn observables_path(app: &App) -> std::path::PathBuf {
+    // Write the observables next to the captured frames.
+    app.project_path()
+        .expect("failed to locate `project_path`")
+        .join(app.exe_name().unwrap())
+        .join("observables")
+        .with_extension("csv")
+}
+
 fn main() {
     nannou::app(model).update(update).run();
 }
@@ -1,9 +1,10 @@
+use lattice::args::arg;
+use lattice::beta_field::{Falloff, Grid, Source};
+use lattice::{Beta, BetaField, Boundary, ClusterRecorder, Clusters, Colouring, LatticeModel, Palette, Recorder, Sampler};
 use nannou::image;
 use nannou::prelude::*;
 use ndarray::prelude::*;
 use ndarray::{Array, Ix2};
-use rand::prelude::*;
-use rand_pcg::Pcg64;
 
 const SEED: u64 = 12345;
 const WINDOW_WIDTH: f32 = 600.0;
@@ -17,16 +18,14 @@ const BETA_START: f32 = 0.1 * BETA_C;
 const BETA_END: f32 = 2.0 * BETA_C;
 const HOTSPOT_PATH_RADIUS: f32 = 150.0;
 const HOTSPOT_RADIUS: f32 = 50.0;
+const FALLOFF: Falloff = Falloff::Linear;
 const N_HOTSPOTS: usize = 3;
 const N_STEPS: usize = 200;
-const N_STATES: usize = 5;
-const RGBAS: [[u8; 4]; N_STATES] = [
-    [255, 48, 50, u8::MAX],
-    [255, 96, 101, u8::MAX],
-    [255, 141, 151, u8::MAX],
-    [255, 192, 203, u8::MAX],
-    [255, 255, 255, u8::MAX]
-];
+const PALETTE: &str = "pink";
+const COLOURING: Colouring = Colouring::State;
+const CLUSTERS: bool = false;
+const SAMPLER: Sampler = Sampler::Metropolis;
+const BOUNDARY: Boundary = Boundary::Periodic;
 
 struct Model {
     _window: window::Id,
@@ -36,15 +35,16 @@ struct Model {
     x1: f32,
     y0: f32,
     y1: f32,
-    n_x: usize,
-    n_y: usize,
-    n_states: usize,
-    rgbas: [[u8; 4]; N_STATES],
-    a: Array<i8, Ix2>,
-    beta: Array<f32, Ix2>,
+    rgbas: Vec<[u8; 4]>,
+    grid: Grid,
+    falloff: Falloff,
+    lattice: LatticeModel,
+    recorder: Recorder,
+    colouring: Colouring,
+    clusters: Clusters,
+    cluster_recorder: Option<ClusterRecorder>,
     hotspots: Vec<[f32; 3]>,
     n_steps: usize,
-    rng: rand_pcg::Pcg64,
     texture: wgpu::Texture,
 }
 
@@ -55,7 +55,6 @@ fn model(app: &App) -> Model {
         .view(view)
         .build()
         .unwrap();
-    let mut rng = Pcg64::seed_from_u64(SEED);
     let window = app.main_window();
 
     let offset_x = (WINDOW_WIDTH - SQUARE_WIDTH) / 2.0;
@@ -70,18 +69,36 @@ fn model(app: &App) -> Model {
     let n_x: usize = SQUARE_WIDTH as usize / w_x as usize;
     let n_y: usize = SQUARE_HEIGHT as usize / w_y as usize;
 
-    let n_states: usize = N_STATES;
-    let rgbas: [[u8; 4]; N_STATES] = RGBAS;
+    // one state per colour, from a named palette, a palette file or a hex list
+    let palette: Palette = arg("palette").unwrap_or_else(|| PALETTE.parse().unwrap());
+    let n_states: usize = palette.len();
+    println!("palette {}, n_states {}", palette, n_states);
+    let rgbas: Vec<[u8; 4]> = palette.colours;
 
     println!("w_x {}, w_y {}, n_x {}, n_y {}", w_x, w_y, n_x, n_y);
-    let mut a = Array::<i8, Ix2>::zeros((n_x, n_y).f());
-    let mut beta = Array::<f32, Ix2>::zeros((n_x, n_y).f());
-    for i in 0..n_x {
-        for j in 0..n_y {
-            a[[i, j]] = rng.gen_range(0..n_states) as i8;
-            beta[[i, j]] = BETA_START;
-        }
-    }
+    let grid = Grid { n_x, n_y, x0, y0, w_x, w_y };
+    let falloff: Falloff = arg("falloff").unwrap_or(FALLOFF);
+    println!("falloff {}", falloff);
+    let beta = Beta::Field(Array::<f32, Ix2>::from_elem((n_x, n_y).f(), BETA_START));
+    let mut lattice = LatticeModel::new(n_x, n_y, n_states, beta, SEED);
+    lattice.randomize();
+    lattice.sampler = arg("sampler").unwrap_or(SAMPLER);
+    lattice.set_boundary(arg("boundary").unwrap_or(BOUNDARY));
+    lattice.couplings = arg("couplings").unwrap_or(lattice.couplings);
+    lattice.n_threads = arg("threads").unwrap_or(lattice.n_threads);
+    println!("sampler {}, threads {}, boundary {}, couplings {}", lattice.sampler, lattice.n_threads, lattice.boundary, lattice.couplings);
+    let recorder = Recorder::create(observables_path(app)).unwrap();
+
+    // colour by state, by cluster size or by a hue kept per cluster, and
+    // optionally write the cluster sizes of every step
+    let colouring: Colouring = arg("colouring").unwrap_or(COLOURING);
+    let clusters = Clusters::label(&lattice);
+    let cluster_recorder = if arg("clusters").unwrap_or(CLUSTERS) {
+        Some(ClusterRecorder::create(clusters_path(app)).unwrap())
+    } else {
+        None
+    };
+    println!("colouring {}, clusters {}", colouring, cluster_recorder.is_some());
     let mut hotspots: Vec<[f32; 3]> = Vec::new();
     let mut theta: f32 = 0.0;
     let x_mid = (x1 + x0) / 2.0;
@@ -108,53 +125,30 @@ fn model(app: &App) -> Model {
         x1,
         y0,
         y1,
-        n_x,
-        n_y,
-        n_states,
         rgbas,
-        a,
-        beta,
+        grid,
+        falloff,
+        lattice,
+        recorder,
+        colouring,
+        clusters,
+        cluster_recorder,
         hotspots,
         n_steps,
-        rng,
         texture,
     }
 }
 
-fn compute_energy(a: &Array<i8, Ix2>, i: usize, j: usize, n_x: usize, n_y: usize) -> f32 {
-    let aij = a[[i, j]];
-    let nn = [
-        a[[(i + n_x - 1) % n_x, j]],
-        a[[(i + 1) % n_x, j]],
-        a[[i, (j + n_y - 1) % n_y]],
-        a[[i, (j + 1) % n_y]]
-    ];
-    let n_same_neighbors = nn.iter().filter(|&n| aij == *n).count();
-    let energy: f32 = 4.0 - 2.0 * (n_same_neighbors as f32);
-    energy
-}
-
 fn update(_app: &App, _model: &mut Model, _update: Update) {
-    let n_x = _model.n_x;
-    let n_y = _model.n_y;
-    for _ in 0..(n_x * n_y) {
-        let i: usize = _model.rng.gen_range(0..n_x);
-        let j: usize = _model.rng.gen_range(0..n_y);
-
-        // compute energy
-        let old_energy = compute_energy(&_model.a, i, j, n_x, n_y);
-
-        // make a move
-        let old_aij = _model.a[[i, j]];
-        _model.a[[i, j]] = _model.rng.gen_range(0.._model.n_states) as i8;
-
-        // compute energy
-        let new_energy = compute_energy(&_model.a, i, j, n_x, n_y);
-
-        // flip a coin and reject if condition is met
-        if _model.rng.gen::<f32>().ln() > (_model.beta[[i, j]] * (old_energy - new_energy) as f32) {
-            _model.a[[i, j]] = old_aij;
-        }
+    _model.lattice.sweep();
+    _model.recorder.record(&_model.lattice).unwrap();
+
+    // relabel clusters, keeping their ids, only when something needs them
+    if _model.colouring != Colouring::State || _model.cluster_recorder.is_some() {
+        _model.clusters.update(&_model.lattice);
+    }
+    if let Some(recorder) = _model.cluster_recorder.as_mut() {
+        recorder.record(&_model.clusters).unwrap();
     }
 
     // evolve hot patches
@@ -172,17 +166,17 @@ fn update(_app: &App, _model: &mut Model, _update: Update) {
     }
 
     // compute new beta
-    for i in 0..n_x {
-        for j in 0..n_y {
-            let x = i as f32 * _model.w_x + _model.x0;
-            let y = j as f32 * _model.w_y + _model.y0;
-            let max_r: f32 = HOTSPOT_RADIUS;
-            let mut r: f32 = max_r;
-            for hs in _model.hotspots.iter() {
-                r = ((hs[0] - x).pow(2) as f32 + (hs[1] - y).pow(2) as f32).sqrt().min(r);
-            }
-            _model.beta[[i, j]] = BETA_START * (1.0 - r / max_r).max(0.0) + BETA_END * (r / max_r).min(1.0);
-        }
+    _model.lattice.beta = Beta::Field(beta_field(_model).evaluate(&_model.grid));
+}
+
+fn beta_field(_model: &Model) -> BetaField {
+    // hot round each hotspot, cold everywhere else
+    BetaField::Distance {
+        source: Source::Points(_model.hotspots.iter().map(|hs| [hs[0], hs[1]]).collect()),
+        radius: HOTSPOT_RADIUS,
+        falloff: _model.falloff,
+        inside: BETA_START,
+        outside: BETA_END,
     }
 }
 
@@ -192,9 +186,15 @@ fn get_rgba(pixel_x: usize, pixel_y: usize, _model: &Model) -> [u8; 4] {
     if _model.x0 <= x && x < _model.x1 && _model.y0 <= y && y < _model.y1 {
         let i: usize = ((x - _model.x0) / _model.w_x) as usize;
         let j: usize = ((y - _model.y0) / _model.w_y) as usize;
-        let val = _model.a[[i, j]] as usize;
-        let rgba = _model.rgbas[val];
+        let rgba = match _model.colouring {
+            Colouring::State => _model.rgbas[_model.lattice.a[[i, j]] as usize],
+            Colouring::Size => _model.clusters.size_colour(i, j, &_model.rgbas),
+            Colouring::Hue => _model.clusters.at(i, j).hue(),
+        };
         rgba
+    } else if let Some(state) = _model.lattice.boundary.fixed_state() {
+        // paint the pinned border around the frame
+        _model.rgbas[state as usize]
     } else {
         let bg: [u8; 4] = [0, 0, 0, u8::MAX];
         bg
@@ -237,6 +237,24 @@ fn captured_frame_path(app: &App, frame: &Frame) -> std::path::PathBuf {
         .with_extension("png")
 }
 
+fn observables_path(app: &App) -> std::path::PathBuf {
+    // Write the observables next to the captured frames.
+    app.project_path()
+        .expect("failed to locate `project_path`")
+        .join(app.exe_name().unwrap())
+        .join("observables")
+        .with_extension("csv")
+}
+
+fn clusters_path(app: &App) -> std::path::PathBuf {
+    // Write the cluster sizes next to the observables.
+    app.project_path()
+        .expect("failed to locate `project_path`")
+        .join(app.exe_name().unwrap())
+        .join("clusters")
+        .with_extension("csv")
+}
+
 fn main() {
     nannou::app(model).update(update).run();
 }
@@ -1,9 +1,11 @@
+use lattice::args::arg;
+use lattice::beta_field::{Falloff, Grid, Source};
+use lattice::{Beta, BetaField, Boundary, LatticeModel, Palette, Recorder, Sampler, Schedule, Shape};
 use nannou::image;
 use nannou::prelude::*;
 use ndarray::prelude::*;
 use ndarray::{Array, Ix2};
-use rand::prelude::*;
-use rand_pcg::Pcg64;
+use std::path::PathBuf;
 
 const SEED: u64 = 12345;
 const WINDOW_WIDTH: f32 = 600.0;
@@ -17,13 +19,13 @@ const BETA_START: f32 = 0.5 * BETA_C;
 const BETA_END: f32 = 2.0 * BETA_C;
 const HOTSPOT_PATH_RADIUS: f32 = 150.0;
 const HOTSPOT_RADIUS: f32 = 50.0;
+const FALLOFF: Falloff = Falloff::Linear;
 const N_HOTSPOTS: usize = 360;
 const N_STEPS: usize = 300;
-const N_STATES: usize = 2;
-const RGBAS: [[u8; 4]; N_STATES] = [
-    [0, 0, 0, u8::MAX],
-    [u8::MAX, u8::MAX, u8::MAX, u8::MAX]
-];
+const SHAPE: Shape = Shape::Linear;
+const PALETTE: &str = "mono";
+const SAMPLER: Sampler = Sampler::Metropolis;
+const BOUNDARY: Boundary = Boundary::Periodic;
 
 struct Model {
     _window: window::Id,
@@ -33,17 +35,15 @@ struct Model {
     x1: f32,
     y0: f32,
     y1: f32,
-    n_x: usize,
-    n_y: usize,
-    n_states: usize,
-    rgbas: [[u8; 4]; N_STATES],
-    a: Array<i8, Ix2>,
-    beta: Array<f32, Ix2>,
-    global_beta: f32,
-    beta_delta: f32,
+    rgbas: Vec<[u8; 4]>,
+    grid: Grid,
+    falloff: Falloff,
+    lattice: LatticeModel,
+    recorder: Recorder,
+    schedule: Schedule,
+    step: usize,
     hotspots: Vec<[f32; 3]>,
     n_steps: usize,
-    rng: rand_pcg::Pcg64,
     texture: wgpu::Texture,
 }
 
@@ -54,7 +54,6 @@ fn model(app: &App) -> Model {
         .view(view)
         .build()
         .unwrap();
-    let rng = Pcg64::seed_from_u64(SEED);
     let window = app.main_window();
 
     let offset_x = (WINDOW_WIDTH - SQUARE_WIDTH) / 2.0;
@@ -69,20 +68,31 @@ fn model(app: &App) -> Model {
     let n_x: usize = SQUARE_WIDTH as usize / w_x as usize;
     let n_y: usize = SQUARE_HEIGHT as usize / w_y as usize;
 
-    let n_states: usize = N_STATES;
-    let rgbas: [[u8; 4]; N_STATES] = RGBAS;
+    // one state per colour, from a named palette, a palette file or a hex list
+    let palette: Palette = arg("palette").unwrap_or_else(|| PALETTE.parse().unwrap());
+    let n_states: usize = palette.len();
+    println!("palette {}, n_states {}", palette, n_states);
+    let rgbas: Vec<[u8; 4]> = palette.colours;
 
     println!("w_x {}, w_y {}, n_x {}, n_y {}", w_x, w_y, n_x, n_y);
-    let mut a = Array::<i8, Ix2>::zeros((n_x, n_y).f());
-    let mut beta = Array::<f32, Ix2>::zeros((n_x, n_y).f());
-    for i in 0..n_x {
-        for j in 0..n_y {
-            a[[i, j]] = 0;
-            beta[[i, j]] = BETA_END;
-        }
-    }
-    let global_beta = BETA_END;
-    let beta_delta: f32 = (BETA_END - BETA_START) / N_STEPS as f32;
+    let grid = Grid { n_x, n_y, x0, y0, w_x, w_y };
+    let falloff: Falloff = arg("falloff").unwrap_or(FALLOFF);
+    println!("falloff {}", falloff);
+    // a loop out to the far end and back, or keyframes from --schedule path
+    let schedule: Schedule = match arg::<PathBuf>("schedule") {
+        Some(path) => Schedule::load(path).unwrap(),
+        None => Schedule::cycle(arg("shape").unwrap_or(SHAPE), BETA_END, BETA_START, 2 * N_STEPS),
+    };
+    println!("schedule {}", schedule);
+    let beta = Beta::Field(Array::<f32, Ix2>::from_elem((n_x, n_y).f(), schedule.at(0)));
+    let mut lattice = LatticeModel::new(n_x, n_y, n_states, beta, SEED);
+    lattice.sampler = arg("sampler").unwrap_or(SAMPLER);
+    lattice.set_boundary(arg("boundary").unwrap_or(BOUNDARY));
+    lattice.couplings = arg("couplings").unwrap_or(lattice.couplings);
+    lattice.n_threads = arg("threads").unwrap_or(lattice.n_threads);
+    println!("sampler {}, threads {}, boundary {}, couplings {}", lattice.sampler, lattice.n_threads, lattice.boundary, lattice.couplings);
+    let recorder = Recorder::create(observables_path(app)).unwrap();
+    let step: usize = 0;
     let mut hotspots: Vec<[f32; 3]> = Vec::new();
     let mut theta: f32 = 0.0;
     let x_mid = (x1 + x0) / 2.0;
@@ -109,56 +119,22 @@ fn model(app: &App) -> Model {
         x1,
         y0,
         y1,
-        n_x,
-        n_y,
-        n_states,
         rgbas,
-        a,
-        beta,
-        global_beta,
-        beta_delta,
+        grid,
+        falloff,
+        lattice,
+        recorder,
+        schedule,
+        step,
         hotspots,
         n_steps,
-        rng,
         texture,
     }
 }
 
-fn compute_energy(a: &Array<i8, Ix2>, i: usize, j: usize, n_x: usize, n_y: usize) -> f32 {
-    let aij = a[[i, j]];
-    let nn = [
-        a[[(i + n_x - 1) % n_x, j]],
-        a[[(i + 1) % n_x, j]],
-        a[[i, (j + n_y - 1) % n_y]],
-        a[[i, (j + 1) % n_y]]
-    ];
-    let n_same_neighbors = nn.iter().filter(|&n| aij == *n).count();
-    let energy: f32 = 4.0 - 2.0 * (n_same_neighbors as f32);
-    energy
-}
-
 fn update(_app: &App, _model: &mut Model, _update: Update) {
-    let n_x = _model.n_x;
-    let n_y = _model.n_y;
-    for _ in 0..(n_x * n_y) {
-        let i: usize = _model.rng.gen_range(0..n_x);
-        let j: usize = _model.rng.gen_range(0..n_y);
-
-        // compute energy
-        let old_energy = compute_energy(&_model.a, i, j, n_x, n_y);
-
-        // make a move
-        let old_aij = _model.a[[i, j]];
-        _model.a[[i, j]] = _model.rng.gen_range(0.._model.n_states) as i8;
-
-        // compute energy
-        let new_energy = compute_energy(&_model.a, i, j, n_x, n_y);
-
-        // flip a coin and reject if condition is met
-        if _model.rng.gen::<f32>().ln() > (_model.beta[[i, j]] * (old_energy - new_energy) as f32) {
-            _model.a[[i, j]] = old_aij;
-        }
-    }
+    _model.lattice.sweep();
+    _model.recorder.record(&_model.lattice).unwrap();
 
     // evolve hot patches
     let delta_theta = 2.0 * PI / (_model.n_steps as f32);
@@ -171,24 +147,24 @@ fn update(_app: &App, _model: &mut Model, _update: Update) {
         hs[2] = theta;
     }
 
-    // increment global beta
-    if _model.global_beta > BETA_END || _model.global_beta < BETA_START {
-        _model.beta_delta *= -1.;
-    }
-    _model.global_beta += _model.beta_delta;
+    // next step's global beta
+    let global_beta = _model.schedule.at(_model.step + 1);
 
     // compute new beta
-    for i in 0..n_x {
-        for j in 0..n_y {
-            let x = i as f32 * _model.w_x + _model.x0;
-            let y = j as f32 * _model.w_y + _model.y0;
-            let max_r: f32 = HOTSPOT_RADIUS;
-            let mut r: f32 = max_r;
-            for hs in _model.hotspots.iter() {
-                r = ((hs[0] - x).pow(2) as f32 + (hs[1] - y).pow(2) as f32).sqrt().min(r);
-            }
-            _model.beta[[i, j]] = BETA_START * (1.0 - r / max_r).max(0.0) + _model.global_beta * (r / max_r).min(1.0);
-        }
+    _model.lattice.beta = Beta::Field(beta_field(_model, global_beta).evaluate(&_model.grid));
+
+    // step
+    _model.step += 1
+}
+
+fn beta_field(_model: &Model, global_beta: f32) -> BetaField {
+    // hot round each hotspot, following the global beta everywhere else
+    BetaField::Distance {
+        source: Source::Points(_model.hotspots.iter().map(|hs| [hs[0], hs[1]]).collect()),
+        radius: HOTSPOT_RADIUS,
+        falloff: _model.falloff,
+        inside: BETA_START,
+        outside: global_beta,
     }
 }
 
@@ -198,9 +174,12 @@ fn get_rgba(pixel_x: usize, pixel_y: usize, _model: &Model) -> [u8; 4] {
     if _model.x0 <= x && x < _model.x1 && _model.y0 <= y && y < _model.y1 {
         let i: usize = ((x - _model.x0) / _model.w_x) as usize;
         let j: usize = ((y - _model.y0) / _model.w_y) as usize;
-        let val = _model.a[[i, j]] as usize;
+        let val = _model.lattice.a[[i, j]] as usize;
         let rgba = _model.rgbas[val];
         rgba
+    } else if let Some(state) = _model.lattice.boundary.fixed_state() {
+        // paint the pinned border around the frame
+        _model.rgbas[state as usize]
     } else {
         let bg: [u8; 4] = [0, 0, 0, u8::MAX];
         bg
@@ -243,6 +222,15 @@ fn captured_frame_path(app: &App, frame: &Frame) -> std::path::PathBuf {
         .with_extension("png")
 }
 
+fn observables_path(app: &App) -> std::path::PathBuf {
+    // Write the observables next to the captured frames.
+    app.project_path()
+        .expect("failed to locate `project_path`")
+        .join(app.exe_name().unwrap())
+        .join("observables")
+        .with_extension("csv")
+}
+
 fn main() {
     nannou::app(model).update(update).run();
 }
@@ -1,10 +1,15 @@
+use lattice::args::arg;
+use lattice::beta_field::{Blend, Falloff, Grid, Source};
+use lattice::outline::{resample, Curve};
+use lattice::{Beta, BetaField, Boundary, Checkpoint, LatticeModel, Palette, Recorder, Sampler};
 use nannou::image;
+use nannou::lyon::path::PathEvent;
 use nannou::prelude::*;
-use nannou::text::FontSize;
+use nannou::text::{self, Font, FontSize};
 use ndarray::prelude::*;
 use ndarray::{Array, Ix2};
-use rand::prelude::*;
-use rand_pcg::Pcg64;
+use std::path::PathBuf;
+use std::str::FromStr;
 
 const SEED: u64 = 12345;
 const WINDOW_WIDTH: f32 = 600.0;
@@ -17,16 +22,18 @@ const BETA_C: f32 = 0.440686793509772; // (2.).sqrt().ln_1p() / 2.;
 const BETA_START: f32 = 0.01 * BETA_C;
 const BETA_END: f32 = 3.0 * BETA_C;
 const HOTSPOT_RADIUS: f32 = 50.0;
+const HOTSPOT_SPACING: f32 = 10.0;
+const FLATTEN_TOLERANCE: f32 = 0.5;
+const FILL: bool = false;
+const FALLOFF: Falloff = Falloff::Linear;
 const N_EQUILIBRATION_STEPS: usize = 100;
 const N_CHARACTER_STEPS: usize = 180;
-const N_STATES: usize = 5;
-const RGBAS: [[u8; 4]; N_STATES] = [
-    [255, 48, 50, u8::MAX],
-    [255, 96, 101, u8::MAX],
-    [255, 141, 151, u8::MAX],
-    [255, 192, 203, u8::MAX],
-    [255, 255, 255, u8::MAX]
-];
+const TEXT: &str = "ABC";
+const UNIT: Unit = Unit::Glyph;
+const MIN_FONT_SIZE: FontSize = 8;
+const PALETTE: &str = "pink";
+const SAMPLER: Sampler = Sampler::Metropolis;
+const BOUNDARY: Boundary = Boundary::Periodic;
 
 struct Model {
     _window: window::Id,
@@ -36,18 +43,23 @@ struct Model {
     x1: f32,
     y0: f32,
     y1: f32,
-    n_x: usize,
-    n_y: usize,
-    n_states: usize,
-    rgbas: [[u8; 4]; N_STATES],
-    a: Array<i8, Ix2>,
-    beta: Array<f32, Ix2>,
+    rgbas: Vec<[u8; 4]>,
+    grid: Grid,
+    falloff: Falloff,
+    lattice: LatticeModel,
+    recorder: Recorder,
     hotspots: Vec<Point2>,
-    characters: Vec<char>,
+    contours: Vec<Vec<[f32; 2]>>,
+    spacing: f32,
+    tolerance: f32,
+    fill: bool,
+    units: Vec<String>,
+    unit_steps: Vec<usize>,
+    font: Option<Font>,
     n_equilibration_steps: usize,
-    n_character_steps: usize,
     step: usize,
-    rng: rand_pcg::Pcg64,
+    first_step: usize,
+    checkpoint_path: Option<PathBuf>,
     texture: wgpu::Texture,
 }
 
@@ -58,7 +70,6 @@ fn model(app: &App) -> Model {
         .view(view)
         .build()
         .unwrap();
-    let mut rng = Pcg64::seed_from_u64(SEED);
     let window = app.main_window();
 
     let offset_x = (WINDOW_WIDTH - SQUARE_WIDTH) / 2.0;
@@ -73,23 +84,81 @@ fn model(app: &App) -> Model {
     let n_x: usize = SQUARE_WIDTH as usize / w_x as usize;
     let n_y: usize = SQUARE_HEIGHT as usize / w_y as usize;
 
-    let n_states: usize = N_STATES;
-    let rgbas: [[u8; 4]; N_STATES] = RGBAS;
+    // one state per colour, from a named palette, a palette file or a hex list
+    let palette: Palette = arg("palette").unwrap_or_else(|| PALETTE.parse().unwrap());
+    let n_states: usize = palette.len();
+    println!("palette {}, n_states {}", palette, n_states);
+    let rgbas: Vec<[u8; 4]> = palette.colours;
 
     println!("w_x {}, w_y {}, n_x {}, n_y {}", w_x, w_y, n_x, n_y);
-    let mut a = Array::<i8, Ix2>::zeros((n_x, n_y).f());
-    let mut beta = Array::<f32, Ix2>::zeros((n_x, n_y).f());
-    for i in 0..n_x {
-        for j in 0..n_y {
-            a[[i, j]] = rng.gen_range(0..n_states) as i8;
-            beta[[i, j]] = BETA_START;
-        }
+    let grid = Grid { n_x, n_y, x0, y0, w_x, w_y };
+    let falloff: Falloff = arg("falloff").unwrap_or(FALLOFF);
+    println!("falloff {}", falloff);
+    let beta = Beta::Field(Array::<f32, Ix2>::from_elem((n_x, n_y).f(), BETA_START));
+    let mut lattice = LatticeModel::new(n_x, n_y, n_states, beta, SEED);
+    lattice.randomize();
+    lattice.sampler = SAMPLER;
+    lattice.set_boundary(BOUNDARY);
+    let mut hotspots: Vec<Point2> = Vec::new();
+    let mut contours: Vec<Vec<[f32; 2]>> = Vec::new();
+    let mut step: usize = 0;
+
+    // pick up from an equilibrated snapshot instead of equilibrating again
+    if let Some(path) = arg::<PathBuf>("resume") {
+        let checkpoint: Checkpoint<(Vec<[f32; 2]>, Vec<Vec<[f32; 2]>>)> = Checkpoint::load(&path).unwrap();
+        lattice = checkpoint.lattice;
+        hotspots = checkpoint.sketch.0.into_iter().map(Point2::from).collect();
+        contours = checkpoint.sketch.1;
+        step = checkpoint.step;
+        println!("resumed {} at step {}", path.display(), step);
+        assert_eq!(
+            rgbas.len(),
+            lattice.n_states,
+            "the palette has {} colours but the checkpoint has {} states",
+            rgbas.len(),
+            lattice.n_states
+        );
     }
-    let hotspots: Vec<Point2> = Vec::new();
-    let characters: Vec<char> = vec!['A', 'B', 'C'];
+    let first_step: usize = step;
+    let checkpoint_path: Option<PathBuf> = arg("checkpoint");
+
+    // options on the command line win over the checkpoint's
+    lattice.sampler = arg("sampler").unwrap_or(lattice.sampler);
+    lattice.set_boundary(arg("boundary").unwrap_or(lattice.boundary));
+    lattice.couplings = arg("couplings").unwrap_or(lattice.couplings);
+    lattice.n_threads = arg("threads").unwrap_or(lattice.n_threads);
+    println!("sampler {}, threads {}, boundary {}, couplings {}", lattice.sampler, lattice.n_threads, lattice.boundary, lattice.couplings);
+    let mut recorder = Recorder::create(observables_path(app)).unwrap();
+    recorder.step = step;
     let n_equilibration_steps: usize = N_EQUILIBRATION_STEPS;
-    let n_character_steps: usize = N_CHARACTER_STEPS;
-    let step: usize = 0;
+
+    // the message, with \n for line breaks, cut into glyphs, words, lines or kept whole
+    let message: String = arg::<String>("text").unwrap_or_else(|| TEXT.to_string()).replace("\\n", "\n");
+    let unit: Unit = arg("unit").unwrap_or(UNIT);
+    let units: Vec<String> = unit.split(&message);
+    assert!(!units.is_empty(), "no text to anneal in {:?}", message);
+
+    // steps per unit, as one count for all or a comma-separated list to cycle through
+    let unit_steps: Vec<usize> = match arg::<String>("steps") {
+        Some(steps) => {
+            let steps: Vec<usize> = steps.split(',').map(|n| n.trim().parse().unwrap()).collect();
+            (0..units.len()).map(|k| steps[k % steps.len()]).collect()
+        }
+        None => vec![N_CHARACTER_STEPS; units.len()],
+    };
+    assert!(unit_steps.iter().all(|&n| n > 0), "every unit needs at least one step");
+
+    // a TTF or OTF file in place of the default font
+    let font: Option<Font> = arg::<PathBuf>("font")
+        .map(|path| text::font::from_file(app.project_path().unwrap().join(path)).unwrap());
+    println!("unit {}, units {:?}, steps {:?}", unit, units, unit_steps);
+
+    // outline sampling, and whether to fill the glyphs in as well
+    let spacing: f32 = arg("spacing").unwrap_or(HOTSPOT_SPACING);
+    assert!(spacing > 0.0, "hotspots need a positive spacing, not {}", spacing);
+    let tolerance: f32 = arg("tolerance").unwrap_or(FLATTEN_TOLERANCE);
+    let fill: bool = arg("fill").unwrap_or(FILL);
+    println!("spacing {}, tolerance {}, fill {}", spacing, tolerance, fill);
 
     let texture = wgpu::TextureBuilder::new()
         .size([WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32])
@@ -105,116 +174,197 @@ fn model(app: &App) -> Model {
         x1,
         y0,
         y1,
-        n_x,
-        n_y,
-        n_states,
         rgbas,
-        a,
-        beta,
+        grid,
+        falloff,
+        lattice,
+        recorder,
         hotspots,
-        characters,
+        contours,
+        spacing,
+        tolerance,
+        fill,
+        units,
+        unit_steps,
+        font,
         n_equilibration_steps,
-        n_character_steps,
         step,
-        rng,
+        first_step,
+        checkpoint_path,
         texture,
     }
 }
 
-fn compute_energy(a: &Array<i8, Ix2>, i: usize, j: usize, n_x: usize, n_y: usize) -> f32 {
-    let aij = a[[i, j]];
-    let nn = [
-        a[[(i + n_x - 1) % n_x, j]],
-        a[[(i + 1) % n_x, j]],
-        a[[i, (j + n_y - 1) % n_y]],
-        a[[i, (j + 1) % n_y]]
-    ];
-    let n_same_neighbors = nn.iter().filter(|&n| aij == *n).count();
-    let energy: f32 = 4.0 - 2.0 * (n_same_neighbors as f32);
-    energy
-}
-
 fn update(_app: &App, _model: &mut Model, _update: Update) {
-    let n_x = _model.n_x;
-    let n_y = _model.n_y;
-    for _ in 0..(n_x * n_y) {
-        let i: usize = _model.rng.gen_range(0..n_x);
-        let j: usize = _model.rng.gen_range(0..n_y);
-
-        // compute energy
-        let old_energy = compute_energy(&_model.a, i, j, n_x, n_y);
-
-        // make a move
-        let old_aij = _model.a[[i, j]];
-        _model.a[[i, j]] = _model.rng.gen_range(0.._model.n_states) as i8;
-
-        // compute energy
-        let new_energy = compute_energy(&_model.a, i, j, n_x, n_y);
-
-        // flip a coin and reject if condition is met
-        if _model.rng.gen::<f32>().ln() > (_model.beta[[i, j]] * (old_energy - new_energy) as f32) {
-            _model.a[[i, j]] = old_aij;
-        }
-    }
+    _model.lattice.sweep();
+    _model.recorder.record(&_model.lattice).unwrap();
 
     if _model.step > _model.n_equilibration_steps {
         // get points from text
-        let index = (_model.step - _model.n_equilibration_steps) / _model.n_character_steps;
-        let c: &str = &_model.characters[index % _model.characters.len()].to_string();
+        let unit: String = current_unit(_model).to_string();
         let rect = Rect::from_x_y_w_h(0.0, 0.0, _model.x1 - _model.x0, _model.y1 - _model.y0);
-        let font_size: FontSize = ((72.0 / 96.0) * rect.h()) as FontSize;
-        let text = text(c).font_size(font_size).center_justify().align_middle_y().build(rect);
+        let text = layout(&unit, _model.font.as_ref(), rect);
+
+        // flatten the outline, curves and all, into closed contours
+        let mut contours: Vec<Vec<[f32; 2]>> = Vec::new();
+        for event in text.path_events() {
+            let curve = match event {
+                PathEvent::Begin { at } => {
+                    contours.push(vec![[at.x, at.y]]);
+                    continue;
+                }
+                PathEvent::Line { from, to } => Curve::Line([from.x, from.y], [to.x, to.y]),
+                PathEvent::Quadratic { from, ctrl, to } => {
+                    Curve::Quadratic([from.x, from.y], [ctrl.x, ctrl.y], [to.x, to.y])
+                }
+                PathEvent::Cubic { from, ctrl1, ctrl2, to } => {
+                    Curve::Cubic([from.x, from.y], [ctrl1.x, ctrl1.y], [ctrl2.x, ctrl2.y], [to.x, to.y])
+                }
+                // contours close back to their first point by themselves
+                PathEvent::End { .. } => continue,
+            };
+            if let Some(contour) = contours.last_mut() {
+                contour.extend_from_slice(&curve.flatten(_model.tolerance)[1..]);
+            }
+        }
 
         // shift for vertical alignment
         let mut min_y = 0.0;
         let mut max_y = 0.0;
-        for event in text.path_events() {
-            let (_, from_y) = event.from().to_tuple();
-            let (_, to_y) = event.to().to_tuple();
-            min_y = min_y.min(from_y).min(to_y);
-            max_y = max_y.max(from_y).max(to_y);
+        for p in contours.iter().flatten() {
+            min_y = p[1].min(min_y);
+            max_y = p[1].max(max_y);
         }
         let shift_y = (max_y + min_y) / 2.0;
+        for p in contours.iter_mut().flatten() {
+            *p = [p[0], -p[1] + shift_y];
+        }
 
-        // fill in hotspots
+        // fill in hotspots, evenly spaced along the outline
         _model.hotspots.clear();
-        for event in text.path_events() {
-            let (from_x, from_y) = event.from().to_tuple();
-            let (to_x, to_y) = event.to().to_tuple();
-            let delta_x = to_x - from_x;
-            let delta_y = to_y - from_y;
-            let r = (delta_x*delta_x + delta_y*delta_y).sqrt();
-            let dr = 10.0;
-            let n_points: usize = (r / dr) as usize;
-            let dx = delta_x / n_points as f32;
-            let dy = delta_y / n_points as f32;
-            let mut x = from_x;
-            let mut y = from_y;
-            for _ in 0..n_points {
-                _model.hotspots.push(Point2::new(x, -y + shift_y));
-                x += dx;
-                y += dy;
-            }
-            _model.hotspots.push(Point2::new(to_x, -to_y + shift_y));
+        for contour in contours.iter() {
+            _model.hotspots.extend(resample(contour, _model.spacing).into_iter().map(Point2::from));
         }
+        _model.contours = contours;
     }
 
     // compute new beta
-    for i in 0..n_x {
-        for j in 0..n_y {
-            let x = i as f32 * _model.w_x + _model.x0;
-            let y = j as f32 * _model.w_y + _model.y0;
-            let max_r: f32 = HOTSPOT_RADIUS;
-            let mut r: f32 = max_r;
-            for hs in _model.hotspots.iter() {
-                r = ((hs[0] - x).pow(2) as f32 + (hs[1] - y).pow(2) as f32).sqrt().min(r);
-            }
-            _model.beta[[i, j]] = BETA_START * (1.0 - r / max_r).max(0.0) + BETA_END * (r / max_r).min(1.0);
+    _model.lattice.beta = Beta::Field(beta_field(_model).evaluate(&_model.grid));
+
+    // step
+    _model.step += 1;
+
+    // save the equilibrated state for later runs to resume from
+    if _model.step == _model.n_equilibration_steps {
+        if let Some(path) = &_model.checkpoint_path {
+            let checkpoint = Checkpoint {
+                step: _model.step,
+                lattice: _model.lattice.clone(),
+                sketch: (
+                    _model.hotspots.iter().map(|hs| hs.to_array()).collect::<Vec<[f32; 2]>>(),
+                    _model.contours.clone(),
+                ),
+            };
+            checkpoint.save(path).unwrap();
         }
     }
+}
 
-    // step
-    _model.step += 1
+/// How the message is cut up, one piece annealed after another.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Unit {
+    Glyph,
+    Word,
+    Line,
+    All,
+}
+
+impl Unit {
+    fn split(&self, message: &str) -> Vec<String> {
+        match self {
+            Unit::Glyph => message.chars().filter(|c| !c.is_whitespace()).map(String::from).collect(),
+            Unit::Word => message.split_whitespace().map(String::from).collect(),
+            Unit::Line => message.lines().map(str::trim).filter(|l| !l.is_empty()).map(String::from).collect(),
+            Unit::All => match message.trim() {
+                "" => Vec::new(),
+                m => vec![m.to_string()],
+            },
+        }
+    }
+}
+
+impl FromStr for Unit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "glyph" => Ok(Unit::Glyph),
+            "word" => Ok(Unit::Word),
+            "line" => Ok(Unit::Line),
+            "all" => Ok(Unit::All),
+            _ => Err(format!("unknown unit {:?}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Unit::Glyph => "glyph",
+            Unit::Word => "word",
+            Unit::Line => "line",
+            Unit::All => "all",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn current_unit(_model: &Model) -> &str {
+    // cycle through the units, each held for its own number of steps
+    let total: usize = _model.unit_steps.iter().sum();
+    let mut t = (_model.step - _model.n_equilibration_steps) % total;
+    for (unit, &steps) in _model.units.iter().zip(_model.unit_steps.iter()) {
+        if t < steps {
+            return unit;
+        }
+        t -= steps;
+    }
+    unreachable!()
+}
+
+fn layout<'a>(s: &'a str, font: Option<&Font>, rect: Rect) -> text::Text<'a> {
+    // start at the size of a single letter and shrink until the lines fit the square
+    let mut font_size: FontSize = ((72.0 / 96.0) * rect.h()) as FontSize;
+    loop {
+        let mut builder = text(s).font_size(font_size).wrap_by_word().center_justify().align_middle_y();
+        if let Some(font) = font {
+            builder = builder.font(font.clone());
+        }
+        let text = builder.build(rect);
+        let bounds = text.bounding_rect();
+        if font_size <= MIN_FONT_SIZE || (bounds.w() <= rect.w() && bounds.h() <= rect.h()) {
+            return text;
+        }
+        font_size = (font_size as f32 * 0.9) as FontSize;
+    }
+}
+
+fn beta_field(_model: &Model) -> BetaField {
+    // hot along the outline of the current text, cold everywhere else
+    let outline = BetaField::Distance {
+        source: Source::Points(_model.hotspots.iter().map(|hs| hs.to_array()).collect()),
+        radius: HOTSPOT_RADIUS,
+        falloff: _model.falloff,
+        inside: BETA_START,
+        outside: BETA_END,
+    };
+    if _model.fill {
+        // and hot all through the glyphs' insides
+        let inside = BetaField::filled(&_model.contours, &_model.grid, BETA_START, BETA_END);
+        BetaField::Blend(Blend::Min, vec![outline, inside])
+    } else {
+        outline
+    }
 }
 
 fn get_rgba(pixel_x: usize, pixel_y: usize, _model: &Model) -> [u8; 4] {
@@ -223,9 +373,12 @@ fn get_rgba(pixel_x: usize, pixel_y: usize, _model: &Model) -> [u8; 4] {
     if _model.x0 <= x && x < _model.x1 && _model.y0 <= y && y < _model.y1 {
         let i: usize = ((x - _model.x0) / _model.w_x) as usize;
         let j: usize = ((y - _model.y0) / _model.w_y) as usize;
-        let val = _model.a[[i, j]] as usize;
+        let val = _model.lattice.a[[i, j]] as usize;
         let rgba = _model.rgbas[val];
         rgba
+    } else if let Some(state) = _model.lattice.boundary.fixed_state() {
+        // paint the pinned border around the frame
+        _model.rgbas[state as usize]
     } else {
         let bg: [u8; 4] = [0, 0, 0, u8::MAX];
         bg
@@ -253,7 +406,7 @@ fn view(app: &App, _model: &Model, frame: Frame) {
 
     // Capture the frame!
     if _model.step > _model.n_equilibration_steps {
-        let file_path = captured_frame_path(app, &frame, _model.n_equilibration_steps);
+        let file_path = captured_frame_path(app, &frame, _model.n_equilibration_steps.saturating_sub(_model.first_step));
         app.main_window().capture_frame(file_path);
     }
 }
@@ -270,6 +423,15 @@ fn captured_frame_path(app: &App, frame: &Frame, offset: usize) -> std::path::Pa
         .with_extension("png")
 }
 
+fn observables_path(app: &App) -> std::path::PathBuf {
+    // Write the observables next to the captured frames.
+    app.project_path()
+        .expect("failed to locate `project_path`")
+        .join(app.exe_name().unwrap())
+        .join("observables")
+        .with_extension("csv")
+}
+
 fn main() {
     nannou::app(model).update(update).run();
 }
@@ -1,8 +1,10 @@
+use lattice::args::arg;
+use lattice::glyph::{self, Glyph};
+use lattice::{Beta, Boundary, LatticeModel, Palette, Recorder, Sampler, Schedule, Shape, Topology};
 use nannou::prelude::*;
-use ndarray::prelude::*;
-use ndarray::{Array, Ix2};
 use rand::prelude::*;
-use rand_pcg::Pcg64;
+use sketch::draw_glyph;
+use std::path::PathBuf;
 
 const SEED: u64 = 12345;
 const WINDOW_WIDTH: f32 = 600.0;
@@ -15,12 +17,13 @@ const BETA_C: f32 = 0.440686793509772; // (2.).sqrt().ln_1p() / 2.;
 const BETA_START: f32 = 0.01 * BETA_C;
 const BETA_END: f32 = 3.0 * BETA_C;
 const N_STEPS: usize = 200;
-const N_STATES: usize = 3;
-const RGBAS: [[u8; 4]; N_STATES] = [
-    [255, 141, 151, u8::MAX],
-    [255, 192, 203, u8::MAX],
-    [255, 255, 255, u8::MAX]
-];
+const SHAPE: Shape = Shape::Linear;
+const PALETTE: &str = "pink-light";
+const GLYPH: &str = "polygon";
+const STROKE_WEIGHT: f32 = 2.0;
+const SAMPLER: Sampler = Sampler::Metropolis;
+const TOPOLOGY: Topology = Topology::Square;
+const BOUNDARY: Boundary = Boundary::Periodic;
 
 #[derive(Copy, Clone)]
 struct Particle {
@@ -44,17 +47,14 @@ struct Model {
     // x1: f32,
     // y0: f32,
     // y1: f32,
-    n_x: usize,
-    n_y: usize,
-    n_states: usize,
-    rgbas: [[u8; 4]; N_STATES],
-    a: Array<i8, Ix2>,
+    rgbas: Vec<[u8; 4]>,
+    glyph: Box<dyn Glyph>,
+    lattice: LatticeModel,
+    recorder: Recorder,
     particles: Vec<Particle>,
-    beta: f32,
-    beta_delta: f32,
+    schedule: Schedule,
     step: usize,
     n_steps: usize,
-    rng: rand_pcg::Pcg64,
 }
 
 fn model(app: &App) -> Model {
@@ -64,7 +64,6 @@ fn model(app: &App) -> Model {
         .view(view)
         .build()
         .unwrap();
-    let rng = Pcg64::seed_from_u64(SEED);
     let window_width = WINDOW_WIDTH as f32;
     let window_height = WINDOW_HEIGHT as f32;
 
@@ -80,24 +79,52 @@ fn model(app: &App) -> Model {
     let n_x: usize = SQUARE_WIDTH as usize / w_x as usize;
     let n_y: usize = SQUARE_HEIGHT as usize / w_y as usize;
 
-    let n_states: usize = N_STATES;
-    let rgbas: [[u8; 4]; N_STATES] = RGBAS;
+    // one state per colour, from a named palette, a palette file or a hex list
+    let palette: Palette = arg("palette").unwrap_or_else(|| PALETTE.parse().unwrap());
+    let n_states: usize = palette.len();
+    println!("palette {}, n_states {}", palette, n_states);
+    let rgbas: Vec<[u8; 4]> = palette.colours;
+
+    // how each site is drawn, by name or from SVG files as svg:a.svg,b.svg
+    let glyph_name: String = arg("glyph").unwrap_or_else(|| GLYPH.to_string());
+    let glyph: Box<dyn Glyph> = glyph::parse(&glyph_name).unwrap();
+    println!("glyph {}", glyph_name);
 
     println!("w_x {}, w_y {}, n_x {}, n_y {}", w_x, w_y, n_x, n_y);
-    let a = Array::<i8, Ix2>::zeros((n_x, n_y).f());
-    let beta = BETA_START;
-    let beta_delta: f32 = (BETA_END - BETA_START) / N_STEPS as f32;
+    // a loop out to the far end and back, or keyframes from --schedule path
+    let schedule: Schedule = match arg::<PathBuf>("schedule") {
+        Some(path) => Schedule::load(path).unwrap(),
+        None => Schedule::cycle(arg("shape").unwrap_or(SHAPE), BETA_START, BETA_END, 2 * N_STEPS),
+    };
+    println!("schedule {}", schedule);
+    let beta = schedule.at(0);
+    let mut lattice = LatticeModel::new(n_x, n_y, n_states, Beta::Global(beta), SEED);
+    lattice.sampler = arg("sampler").unwrap_or(SAMPLER);
+    lattice.topology = arg("topology").unwrap_or(TOPOLOGY);
+    lattice.set_boundary(arg("boundary").unwrap_or(BOUNDARY));
+    lattice.couplings = arg("couplings").unwrap_or(lattice.couplings);
+    println!("sampler {}, topology {}, boundary {}, couplings {}", lattice.sampler, lattice.topology, lattice.boundary, lattice.couplings);
+
+    // scale and centre cells so the lattice fills the square in any topology
+    let [extent_x, extent_y] = lattice.topology.extent(n_x, n_y);
+    let fit = (SQUARE_WIDTH / (extent_x * w_x)).min(SQUARE_HEIGHT / (extent_y * w_y));
+    let w_x = w_x * fit;
+    let w_y = w_y * fit;
+    let x0 = x0 + 0.5 * (SQUARE_WIDTH - extent_x * w_x);
+    let y0 = y0 + 0.5 * (SQUARE_HEIGHT - extent_y * w_y);
+    let recorder = Recorder::create(observables_path(app)).unwrap();
     let step: usize = 0;
     let n_steps = N_STEPS;
 
     let mut particles: Vec<Particle> = Vec::new();
     for i in 0..n_x {
         for j in 0..n_y {
+            let [px, py] = lattice.topology.position(i, j);
             let particle = Particle{
-                x0: x0 + (i as f32 + 0.5 ) * w_x,
-                y0: y0 + (j as f32 + 0.5 ) * w_y,
-                x: x0 + (i as f32 + 0.5 ) * w_x,
-                y: y0 + (j as f32 + 0.5 ) * w_y,
+                x0: x0 + px * w_x,
+                y0: y0 + py * w_y,
+                x: x0 + px * w_x,
+                y: y0 + py * w_y,
                 w: w_x,
                 h: w_y,
                 i: i,
@@ -117,61 +144,24 @@ fn model(app: &App) -> Model {
         // x1,
         // y0,
         // y1,
-        n_x,
-        n_y,
-        n_states,
         rgbas,
-        a,
+        glyph,
+        lattice,
+        recorder,
         particles,
-        beta,
-        beta_delta,
+        schedule,
         step,
         n_steps,
-        rng,
     }
 }
 
-fn compute_energy(a: &Array<i8, Ix2>, i: usize, j: usize, n_x: usize, n_y: usize) -> f32 {
-    let aij = a[[i, j]];
-    let nn = [
-        a[[(i + n_x - 1) % n_x, j]],
-        a[[(i + 1) % n_x, j]],
-        a[[i, (j + n_y - 1) % n_y]],
-        a[[i, (j + 1) % n_y]]
-    ];
-    let n_same_neighbors = nn.iter().filter(|&n| aij == *n).count();
-    let energy: f32 = 4.0 - 2.0 * (n_same_neighbors as f32);
-    energy
-}
-
 fn update(_app: &App, _model: &mut Model, _update: Update) {
-    let n_x = _model.n_x;
-    let n_y = _model.n_y;
-    for _ in 0..(n_x * n_y) {
-        let i: usize = _model.rng.gen_range(0..n_x);
-        let j: usize = _model.rng.gen_range(0..n_y);
-
-        // compute energy
-        let old_energy = compute_energy(&_model.a, i, j, n_x, n_y);
-
-        // make a move
-        let old_aij = _model.a[[i, j]];
-        _model.a[[i, j]] = _model.rng.gen_range(0.._model.n_states) as i8;
+    _model.lattice.sweep();
+    _model.recorder.record(&_model.lattice).unwrap();
 
-        // compute energy
-        let new_energy = compute_energy(&_model.a, i, j, n_x, n_y);
-
-        // flip a coin and reject if condition is met
-        if _model.rng.gen::<f32>().ln() > (_model.beta * (old_energy - new_energy) as f32) {
-            _model.a[[i, j]] = old_aij;
-        }
-    }
-
-    // increment beta
-    if _model.beta > BETA_END || _model.beta < BETA_START {
-        _model.beta_delta *= -1.;
-    }
-    _model.beta += _model.beta_delta;
+    // next step's beta
+    let beta = _model.schedule.at(_model.step + 1);
+    _model.lattice.beta = Beta::Global(beta);
 
     // evolve particles
     let wave = 2.0 * (2.0 * PI * _model.step as f32 / _model.n_steps as f32).cos().abs();
@@ -179,8 +169,8 @@ fn update(_app: &App, _model: &mut Model, _update: Update) {
         let x = particle.x/_model.window_width + 0.5;
         let y = particle.y/_model.window_height + 0.5;
         if y + x > wave {
-            particle.x += _model.rng.gen_range(-10.0..10.0);
-            particle.y += _model.rng.gen_range(-10.0..10.0);
+            particle.x += _model.lattice.rng.gen_range(-10.0..10.0);
+            particle.y += _model.lattice.rng.gen_range(-10.0..10.0);
         } else {
             particle.x = particle.x0;
             particle.y = particle.y0;
@@ -191,38 +181,12 @@ fn update(_app: &App, _model: &mut Model, _update: Update) {
     _model.step += 1
 }
 
-fn get_color(_model: &Model, index: usize) -> Rgba<u8> {
-    let color = rgba(
-        _model.rgbas[index][0],
-        _model.rgbas[index][1],
-        _model.rgbas[index][2],
-        _model.rgbas[index][3],
-    );
-    color
-}
-
 fn view(app: &App, _model: &Model, frame: Frame) {
     let draw = app.draw();
     draw.background().color(BLACK);
     for particle in _model.particles.iter() {
-        let val = _model.a[[particle.i, particle.j]] as usize;
-        let n_points = match val {
-            0 => 2,
-            1 => 3,
-            2 => 5,
-            _ => 3
-        };
-        let points = (0..=360).step_by(360 / n_points).map(|theta| {
-           let radian = deg_to_rad(theta as f32);
-           let x = particle.x + 0.5 * radian.sin() * particle.w;
-           let y = particle.y + 0.5 * radian.cos() * particle.h;
-           pt2(x,y)
-        });
-        draw.polygon()
-            .no_fill()
-            .stroke_color(get_color(_model, val))
-            .stroke_weight(2.0)
-            .points(points);
+        let val = _model.lattice.a[[particle.i, particle.j]] as usize;
+        draw_glyph(&draw, _model.glyph.as_ref(), &_model.rgbas, STROKE_WEIGHT, val, pt2(particle.x, particle.y), vec2(particle.w, particle.h));
     }
     draw.to_frame(app, &frame).unwrap();
 
@@ -243,6 +207,15 @@ fn captured_frame_path(app: &App, frame: &Frame, offset: usize) -> std::path::Pa
         .with_extension("png")
 }
 
+fn observables_path(app: &App) -> std::path::PathBuf {
+    // Write the observables next to the captured frames.
+    app.project_path()
+        .expect("failed to locate `project_path`")
+        .join(app.exe_name().unwrap())
+        .join("observables")
+        .with_extension("csv")
+}
+
 fn main() {
     nannou::app(model).update(update).run();
 }
@@ -1,8 +1,10 @@
+use lattice::args::arg;
+use lattice::glyph::{self, Glyph};
+use lattice::{Beta, Boundary, DomainMap, LatticeModel, Palette, Recorder, Sampler, Schedule, Shape, Smoothing};
+use nannou::lyon;
 use nannou::prelude::*;
-use ndarray::prelude::*;
-use ndarray::{Array, Ix2};
-use rand::prelude::*;
-use rand_pcg::Pcg64;
+use sketch::draw_glyph;
+use std::path::PathBuf;
 
 const SEED: u64 = 12345;
 const WINDOW_WIDTH: f32 = 600.0;
@@ -15,12 +17,17 @@ const BETA_C: f32 = 0.440686793509772; // (2.).sqrt().ln_1p() / 2.;
 const BETA_START: f32 = 0.75 * BETA_C;
 const BETA_END: f32 = 1.25 * BETA_C;
 const N_EQUILIBRATION_STEPS: usize = 100;
-const N_STATES: usize = 3;
-const RGBAS: [[u8; 4]; N_STATES] = [
-    [102, 124, 38, u8::MAX],
-    [56, 124, 68, u8::MAX],
-    [37, 65, 23, u8::MAX],
-];
+const SHAPE: Shape = Shape::Linear;
+const PALETTE: &str = "forest";
+const GLYPH: &str = "leaf";
+const STROKE_WEIGHT: f32 = 2.0;
+const DOMAINS: bool = false;
+const FILL: bool = false;
+const SMOOTHING: Smoothing = Smoothing::Chaikin(3);
+const SVG: bool = false;
+const SVG_STROKE_WIDTH: f32 = 0.1;
+const SAMPLER: Sampler = Sampler::Metropolis;
+const BOUNDARY: Boundary = Boundary::Periodic;
 
 struct Model {
     _window: window::Id,
@@ -30,15 +37,16 @@ struct Model {
     // x1: f32,
     y0: f32,
     // y1: f32,
-    n_x: usize,
-    n_y: usize,
-    n_states: usize,
-    rgbas: [[u8; 4]; N_STATES],
-    a: Array<i8, Ix2>,
-    beta: f32,
-    beta_delta: f32,
+    rgbas: Vec<[u8; 4]>,
+    glyph: Box<dyn Glyph>,
+    domains: bool,
+    fill: bool,
+    smoothing: Smoothing,
+    svg: bool,
+    lattice: LatticeModel,
+    recorder: Recorder,
+    schedule: Schedule,
     step: usize,
-    rng: rand_pcg::Pcg64,
 }
 
 fn model(app: &App) -> Model {
@@ -48,7 +56,6 @@ fn model(app: &App) -> Model {
         .view(view)
         .build()
         .unwrap();
-    let mut rng = Pcg64::seed_from_u64(SEED);
 
     let offset_x = (WINDOW_WIDTH - SQUARE_WIDTH) / 2.0;
     let offset_y = (WINDOW_HEIGHT - SQUARE_HEIGHT) / 2.0;
@@ -62,18 +69,40 @@ fn model(app: &App) -> Model {
     let n_x: usize = SQUARE_WIDTH as usize / w_x as usize;
     let n_y: usize = SQUARE_HEIGHT as usize / w_y as usize;
 
-    let n_states: usize = N_STATES;
-    let rgbas: [[u8; 4]; N_STATES] = RGBAS;
+    // one state per colour, from a named palette, a palette file or a hex list
+    let palette: Palette = arg("palette").unwrap_or_else(|| PALETTE.parse().unwrap());
+    let n_states: usize = palette.len();
+    println!("palette {}, n_states {}", palette, n_states);
+    let rgbas: Vec<[u8; 4]> = palette.colours;
+
+    // how each site is drawn, by name or from SVG files as svg:a.svg,b.svg
+    let glyph_name: String = arg("glyph").unwrap_or_else(|| GLYPH.to_string());
+    let glyph: Box<dyn Glyph> = glyph::parse(&glyph_name).unwrap();
+    println!("glyph {}", glyph_name);
+
+    // domain walls drawn as smoothed outlines in place of glyphs, and
+    // written out as SVG next to each captured frame
+    let domains: bool = arg("domains").unwrap_or(DOMAINS);
+    let fill: bool = arg("fill").unwrap_or(FILL);
+    let smoothing: Smoothing = arg("smoothing").unwrap_or(SMOOTHING);
+    let svg: bool = arg("svg").unwrap_or(SVG);
+    println!("domains {}, fill {}, smoothing {}, svg {}", domains, fill, smoothing, svg);
 
     println!("w_x {}, w_y {}, n_x {}, n_y {}", w_x, w_y, n_x, n_y);
-    let mut a = Array::<i8, Ix2>::zeros((n_x, n_y).f());
-    for i in 0..n_x {
-        for j in 0..n_y {
-            a[[i,j]] = rng.gen_range(0..n_states) as i8;
-        }
-    }
-    let beta = BETA_START;
-    let beta_delta: f32 = (BETA_END - BETA_START) / N_EQUILIBRATION_STEPS as f32;
+    // a loop out to the far end and back, or keyframes from --schedule path
+    let schedule: Schedule = match arg::<PathBuf>("schedule") {
+        Some(path) => Schedule::load(path).unwrap(),
+        None => Schedule::cycle(arg("shape").unwrap_or(SHAPE), BETA_START, BETA_END, 2 * N_EQUILIBRATION_STEPS),
+    };
+    println!("schedule {}", schedule);
+    let beta = schedule.at(0);
+    let mut lattice = LatticeModel::new(n_x, n_y, n_states, Beta::Global(beta), SEED);
+    lattice.randomize();
+    lattice.sampler = arg("sampler").unwrap_or(SAMPLER);
+    lattice.set_boundary(arg("boundary").unwrap_or(BOUNDARY));
+    lattice.couplings = arg("couplings").unwrap_or(lattice.couplings);
+    println!("sampler {}, boundary {}, couplings {}", lattice.sampler, lattice.boundary, lattice.couplings);
+    let recorder = Recorder::create(observables_path(app)).unwrap();
     let step: usize = 0;
 
     Model {
@@ -84,59 +113,26 @@ fn model(app: &App) -> Model {
         // x1,
         y0,
         // y1,
-        n_x,
-        n_y,
-        n_states,
         rgbas,
-        a,
-        beta,
-        beta_delta,
+        glyph,
+        domains,
+        fill,
+        smoothing,
+        svg,
+        lattice,
+        recorder,
+        schedule,
         step,
-        rng,
     }
 }
 
-fn compute_energy(a: &Array<i8, Ix2>, i: usize, j: usize, n_x: usize, n_y: usize) -> f32 {
-    let aij = a[[i, j]];
-    let nn = [
-        a[[(i + n_x - 1) % n_x, j]],
-        a[[(i + 1) % n_x, j]],
-        a[[i, (j + n_y - 1) % n_y]],
-        a[[i, (j + 1) % n_y]]
-    ];
-    let n_same_neighbors = nn.iter().filter(|&n| aij == *n).count();
-    let energy: f32 = 4.0 - 2.0 * (n_same_neighbors as f32);
-    energy
-}
-
 fn update(_app: &App, _model: &mut Model, _update: Update) {
-    let n_x = _model.n_x;
-    let n_y = _model.n_y;
-    for _ in 0..(n_x * n_y) {
-        let i: usize = _model.rng.gen_range(0..n_x);
-        let j: usize = _model.rng.gen_range(0..n_y);
-
-        // compute energy
-        let old_energy = compute_energy(&_model.a, i, j, n_x, n_y);
-
-        // make a move
-        let old_aij = _model.a[[i, j]];
-        _model.a[[i, j]] = _model.rng.gen_range(0.._model.n_states) as i8;
+    _model.lattice.sweep();
+    _model.recorder.record(&_model.lattice).unwrap();
 
-        // compute energy
-        let new_energy = compute_energy(&_model.a, i, j, n_x, n_y);
-
-        // flip a coin and reject if condition is met
-        if _model.rng.gen::<f32>().ln() > (_model.beta * (old_energy - new_energy) as f32) {
-            _model.a[[i, j]] = old_aij;
-        }
-    }
-
-    // increment beta
-    if _model.beta > BETA_END || _model.beta < BETA_START {
-        _model.beta_delta *= -1.;
-    }
-    _model.beta += _model.beta_delta;
+    // next step's beta
+    let beta = _model.schedule.at(_model.step + 1);
+    _model.lattice.beta = Beta::Global(beta);
 
     // step
     _model.step += 1
@@ -152,31 +148,52 @@ fn get_color(_model: &Model, index: usize) -> Rgba<u8> {
     color
 }
 
+fn draw_domains(draw: &Draw, _model: &Model, map: &DomainMap) {
+    // one path per state, holes and all, in cell units from the lattice corner
+    for (state, contours) in map.contours.iter().enumerate() {
+        let at = |p: [f32; 2]| lyon::math::point(_model.x0 + p[0] * _model.w_x, _model.y0 + p[1] * _model.w_y);
+        let mut builder = nannou::geom::path::Builder::new().with_svg();
+        for contour in contours.iter().filter(|c| c.len() > 2) {
+            builder.move_to(at(contour[0]));
+            for &p in contour[1..].iter() {
+                builder.line_to(at(p));
+            }
+            builder.close();
+        }
+        let path = builder.build();
+        if _model.fill {
+            draw.path()
+                .fill()
+                .color(get_color(_model, state))
+                .events(path.iter());
+        }
+        draw.path()
+            .stroke()
+            .weight(STROKE_WEIGHT)
+            .color(get_color(_model, state))
+            .events(path.iter());
+    }
+}
+
 fn view(app: &App, _model: &Model, frame: Frame) {
     let draw = app.draw();
     draw.background().color(BLACK);
-    for i in 0.._model.n_x {
-        for j in 0.._model.n_y {
-            let val = _model.a[[i, j]] as usize;
-            let leaf_size = match val {
-                0 => 40.0,
-                1 => 60.0,
-                2 => 80.0,
-                _ => 30.0
-            };
-            let leaf_rotation = match val {
-                0 => PI/2.5,
-                1 => PI/3.0,
-                2 => PI/4.5,
-                _ => PI/2.5
-            };
-            draw.ellipse()
-                .color(get_color(_model, val))
-                .w(leaf_size)
-                .h(2.0)
-                .rotate(leaf_rotation)
-                .x(_model.x0 + (i as f32 + 0.5) * _model.w_x)
-                .y(_model.y0 + (j as f32 + 0.5) * _model.w_y);
+    let map = if _model.domains || _model.svg {
+        Some(DomainMap::trace(&_model.lattice).smoothed(_model.smoothing))
+    } else {
+        None
+    };
+    match &map {
+        Some(map) if _model.domains => draw_domains(&draw, _model, map),
+        _ => {
+            for i in 0.._model.lattice.n_x {
+                for j in 0.._model.lattice.n_y {
+                    let val = _model.lattice.a[[i, j]] as usize;
+                    let x = _model.x0 + (i as f32 + 0.5) * _model.w_x;
+                    let y = _model.y0 + (j as f32 + 0.5) * _model.w_y;
+                    draw_glyph(&draw, _model.glyph.as_ref(), &_model.rgbas, STROKE_WEIGHT, val, pt2(x, y), vec2(_model.w_x, _model.w_y));
+                }
+            }
         }
     }
     draw.to_frame(app, &frame).unwrap();
@@ -184,6 +201,12 @@ fn view(app: &App, _model: &Model, frame: Frame) {
     // Capture the frame!
     let file_path = captured_frame_path(app, &frame, 0);
     app.main_window().capture_frame(file_path);
+
+    // and its domain walls, the lattice at the size it has in the PNG
+    if let Some(map) = map.filter(|_| _model.svg) {
+        let svg_path = captured_frame_path(app, &frame, 0).with_extension("svg");
+        map.save_svg(svg_path, &_model.rgbas, _model.w_x, SVG_STROKE_WIDTH).unwrap();
+    }
 }
 
 fn captured_frame_path(app: &App, frame: &Frame, offset: usize) -> std::path::PathBuf {
@@ -198,6 +221,15 @@ fn captured_frame_path(app: &App, frame: &Frame, offset: usize) -> std::path::Pa
         .with_extension("png")
 }
 
+fn observables_path(app: &App) -> std::path::PathBuf {
+    // Write the observables next to the captured frames.
+    app.project_path()
+        .expect("failed to locate `project_path`")
+        .join(app.exe_name().unwrap())
+        .join("observables")
+        .with_extension("csv")
+}
+
 fn main() {
     nannou::app(model).update(update).run();
 }
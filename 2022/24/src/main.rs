@@ -1,9 +1,10 @@
+use lattice::args::arg;
+use lattice::glyph::{self, Glyph};
+use lattice::random::{report, Generator, RandomSource};
+use lattice::{Beta, LatticeModel, Palette, Recorder, Sampler, Schedule, Shape};
 use nannou::prelude::*;
-use ndarray::prelude::*;
-use ndarray::{Array, Ix2};
-use rand::prelude::*;
-use rand_pcg::Pcg64;
-use std::time::Instant;
+use sketch::draw_glyph;
+use std::path::PathBuf;
 
 const SEED: u64 = 12345;
 const WINDOW_WIDTH: f32 = 600.0;
@@ -16,12 +17,14 @@ const BETA_C: f32 = 0.440686793509772; // (2.).sqrt().ln_1p() / 2.;
 const BETA_START: f32 = 0.01 * BETA_C;
 const BETA_END: f32 = 3.0 * BETA_C;
 const N_EQUILIBRATION_STEPS: usize = 100;
-const N_STATES: usize = 3;
-const RGBAS: [[u8; 4]; N_STATES] = [
-    [255, 141, 151, u8::MAX],
-    [255, 192, 203, u8::MAX],
-    [255, 255, 255, u8::MAX]
-];
+const SHAPE: Shape = Shape::Linear;
+const PALETTE: &str = "pink-light";
+const GLYPH: &str = "polygon";
+const STROKE_WEIGHT: f32 = 2.0;
+const GENERATORS: [Generator; 2] = [Generator::Pcg, Generator::Time];
+const N_REPORT_SAMPLES: usize = 100000;
+const N_REPORT_BINS: usize = 100;
+const SAMPLER: Sampler = Sampler::Metropolis;
 
 struct Model {
     _window: window::Id,
@@ -31,17 +34,13 @@ struct Model {
     x1: f32,
     y0: f32,
     // y1: f32,
-    n_x: usize,
-    n_y: usize,
-    n_states: usize,
-    rgbas: [[u8; 4]; N_STATES],
-    a0: Array<i8, Ix2>,
-    a1: Array<i8, Ix2>,
-    beta: f32,
-    beta_delta: f32,
+    rgbas: Vec<[u8; 4]>,
+    glyph: Box<dyn Glyph>,
+    lattices: Vec<LatticeModel>,
+    sources: Vec<Box<dyn RandomSource + Send>>,
+    recorders: Vec<Recorder>,
+    schedule: Schedule,
     step: usize,
-    rng: rand_pcg::Pcg64,
-    now: Instant
 }
 
 fn model(app: &App) -> Model {
@@ -51,7 +50,6 @@ fn model(app: &App) -> Model {
         .view(view)
         .build()
         .unwrap();
-    let rng = Pcg64::seed_from_u64(SEED);
 
     let offset_x = (WINDOW_WIDTH - SQUARE_WIDTH) / 2.0;
     let offset_y = (WINDOW_HEIGHT - SQUARE_HEIGHT) / 2.0;
@@ -62,19 +60,58 @@ fn model(app: &App) -> Model {
 
     let w_x: f32 = SPIN_WIDTH_X;
     let w_y: f32 = SPIN_WIDTH_Y;
-    let n_x: usize = (0.5 * SQUARE_WIDTH) as usize / w_x as usize;
+
+    // one panel per generator, side by side, with --generators pcg,lcg,sobol
+    let generators: Vec<Generator> = match arg::<String>("generators") {
+        Some(names) => names.split(',').map(|name| name.parse().unwrap()).collect(),
+        None => GENERATORS.to_vec(),
+    };
+    let n_panels = generators.len();
+    let n_x: usize = (SQUARE_WIDTH / n_panels as f32) as usize / w_x as usize;
     let n_y: usize = SQUARE_HEIGHT as usize / w_y as usize;
 
-    let n_states: usize = N_STATES;
-    let rgbas: [[u8; 4]; N_STATES] = RGBAS;
+    // one state per colour, from a named palette, a palette file or a hex list
+    let palette: Palette = arg("palette").unwrap_or_else(|| PALETTE.parse().unwrap());
+    let n_states: usize = palette.len();
+    println!("palette {}, n_states {}", palette, n_states);
+    let rgbas: Vec<[u8; 4]> = palette.colours;
+
+    // how each site is drawn, by name or from SVG files as svg:a.svg,b.svg
+    let glyph_name: String = arg("glyph").unwrap_or_else(|| GLYPH.to_string());
+    let glyph: Box<dyn Glyph> = glyph::parse(&glyph_name).unwrap();
+    println!("glyph {}", glyph_name);
 
     println!("w_x {}, w_y {}, n_x {}, n_y {}", w_x, w_y, n_x, n_y);
-    let a0 = Array::<i8, Ix2>::zeros((n_x, n_y).f());
-    let a1 = Array::<i8, Ix2>::zeros((n_x, n_y).f());
-    let beta = BETA_START;
-    let beta_delta: f32 = (BETA_END - BETA_START) / N_EQUILIBRATION_STEPS as f32;
+
+    // measure each generator on a stream of its own before it drives a lattice
+    for generator in generators.iter() {
+        let mut source = generator.source(SEED);
+        println!("{}: {}", generator, report(&mut *source, N_REPORT_SAMPLES, N_REPORT_BINS));
+    }
+
+    // a loop out to the far end and back, or keyframes from --schedule path
+    let schedule: Schedule = match arg::<PathBuf>("schedule") {
+        Some(path) => Schedule::load(path).unwrap(),
+        None => Schedule::cycle(arg("shape").unwrap_or(SHAPE), BETA_START, BETA_END, 2 * N_EQUILIBRATION_STEPS),
+    };
+    println!("schedule {}", schedule);
+    let beta = schedule.at(0);
+    let sampler: Sampler = arg("sampler").unwrap_or(SAMPLER);
+    println!("sampler {}", sampler);
+    let lattices: Vec<LatticeModel> = generators
+        .iter()
+        .map(|_| {
+            let mut lattice = LatticeModel::new(n_x, n_y, n_states, Beta::Global(beta), SEED);
+            lattice.sampler = sampler;
+            lattice
+        })
+        .collect();
+    let sources: Vec<Box<dyn RandomSource + Send>> = generators.iter().map(|generator| generator.source(SEED)).collect();
+    let recorders: Vec<Recorder> = generators
+        .iter()
+        .map(|generator| Recorder::create(observables_path(app, &format!("observables_{}", generator))).unwrap())
+        .collect();
     let step: usize = 0;
-    let now = Instant::now();
 
     Model {
         _window,
@@ -84,133 +121,51 @@ fn model(app: &App) -> Model {
         x1,
         y0,
         // y1,
-        n_x,
-        n_y,
-        n_states,
         rgbas,
-        a0,
-        a1,
-        beta,
-        beta_delta,
+        glyph,
+        lattices,
+        sources,
+        recorders,
+        schedule,
         step,
-        rng,
-        now
     }
 }
 
-fn compute_energy(a: &Array<i8, Ix2>, i: usize, j: usize, n_x: usize, n_y: usize) -> f32 {
-    let aij = a[[i, j]];
-    let nn = [
-        a[[(i + n_x - 1) % n_x, j]],
-        a[[(i + 1) % n_x, j]],
-        a[[i, (j + n_y - 1) % n_y]],
-        a[[i, (j + 1) % n_y]]
-    ];
-    let n_same_neighbors = nn.iter().filter(|&n| aij == *n).count();
-    let energy: f32 = 4.0 - 2.0 * (n_same_neighbors as f32);
-    energy
-}
-
-fn rng_gen(now: &Instant) -> f32 {
-    let duration = now.elapsed().as_nanos() as f32;
-    let x = (duration.cos() + 1.0) / 2.0;
-    x
-}
-
-fn rng_gen_range(now: &Instant, range: std::ops::Range<usize>) -> usize {
-    let x = rng_gen(now);
-    let val = (range.start + (x * (range.end - range.start) as f32) as usize).min(range.end - 1);
-    val
-}
-
 fn update(_app: &App, _model: &mut Model, _update: Update) {
-    let n_x = _model.n_x;
-    let n_y = _model.n_y;
-    for _ in 0..(n_x * n_y) {
-        let i: usize = _model.rng.gen_range(0..n_x);
-        let j: usize = _model.rng.gen_range(0..n_y);
-
-        let old_energy0 = compute_energy(&_model.a0, i, j, n_x, n_y);
-        let old_aij0 = _model.a0[[i, j]];
-        _model.a0[[i, j]] = _model.rng.gen_range(0.._model.n_states) as i8;
-        let new_energy0 = compute_energy(&_model.a0, i, j, n_x, n_y);
-        // flip a coin and reject if condition is met
-        if _model.rng.gen::<f32>().ln() > (_model.beta * (old_energy0 - new_energy0) as f32) {
-            _model.a0[[i, j]] = old_aij0;
-        }
-
-        let old_energy1 = compute_energy(&_model.a1, i, j, n_x, n_y);
-        let old_aij1 = _model.a1[[i, j]];
-        _model.a1[[i, j]] = rng_gen_range(&_model.now, 0.._model.n_states) as i8;
-        let new_energy1 = compute_energy(&_model.a1, i, j, n_x, n_y);
-        // flip a coin and reject if condition is met
-        if rng_gen(&_model.now).ln() > (_model.beta * (old_energy1 - new_energy1) as f32) {
-            _model.a1[[i, j]] = old_aij1;
-        }
+    // every draw of a panel's sweep, sites included, comes from its generator
+    for ((lattice, source), recorder) in _model
+        .lattices
+        .iter_mut()
+        .zip(_model.sources.iter_mut())
+        .zip(_model.recorders.iter_mut())
+    {
+        lattice.sweep_with(&mut **source);
+        recorder.record(lattice).unwrap();
     }
 
-    // increment beta
-    if _model.beta > BETA_END || _model.beta < BETA_START {
-        _model.beta_delta *= -1.;
+    // next step's beta
+    let beta = _model.schedule.at(_model.step + 1);
+    for lattice in _model.lattices.iter_mut() {
+        lattice.beta = Beta::Global(beta);
     }
-    _model.beta += _model.beta_delta;
 
     // step
     _model.step += 1
 }
 
-fn get_color(_model: &Model, index: usize) -> Rgba<u8> {
-    let color = rgba(
-        _model.rgbas[index][0],
-        _model.rgbas[index][1],
-        _model.rgbas[index][2],
-        _model.rgbas[index][3],
-    );
-    color
-}
-
 fn view(app: &App, _model: &Model, frame: Frame) {
     let draw = app.draw();
     draw.background().color(BLACK);
-    for i in 0.._model.n_x {
-        for j in 0.._model.n_y {
-            let val0 = _model.a0[[i, j]] as usize;
-            let n_points0 = match val0 {
-                0 => 2,
-                1 => 3,
-                2 => 5,
-                _ => 3
-            };
-            let points0 = (0..=360).step_by(360 / n_points0).map(|theta| {
-               let radian = deg_to_rad(theta as f32);
-               let x = _model.x0 + (i as f32 + 0.5 + 0.5 * radian.sin()) * _model.w_x;
-               let y = _model.y0 + (j as f32 + 0.5 + 0.5 * radian.cos()) * _model.w_y;
-               pt2(x,y)
-            });
-            draw.polygon()
-                .no_fill()
-                .stroke_color(get_color(_model, val0))
-                .stroke_weight(2.0)
-                .points(points0);
-
-            let val1 = _model.a1[[i, j]] as usize;
-            let n_points1 = match val1 {
-                0 => 2,
-                1 => 3,
-                2 => 5,
-                _ => 3
-            };
-            let points1 = (0..=360).step_by(360 / n_points1).map(|theta| {
-               let radian = deg_to_rad(theta as f32);
-               let x = _model.x0 + 0.5 * (_model.x1 - _model.x0) + (i as f32 + 0.5 + 0.5 * radian.sin()) * _model.w_x;
-               let y = _model.y0 + (j as f32 + 0.5 + 0.5 * radian.cos()) * _model.w_y;
-               pt2(x,y)
-            });
-            draw.polygon()
-                .no_fill()
-                .stroke_color(get_color(_model, val1))
-                .stroke_weight(2.0)
-                .points(points1);
+    let panel_width = (_model.x1 - _model.x0) / _model.lattices.len() as f32;
+    for (k, lattice) in _model.lattices.iter().enumerate() {
+        let panel_x0 = _model.x0 + k as f32 * panel_width;
+        for i in 0..lattice.n_x {
+            for j in 0..lattice.n_y {
+                let val = lattice.a[[i, j]] as usize;
+                let x = panel_x0 + (i as f32 + 0.5) * _model.w_x;
+                let y = _model.y0 + (j as f32 + 0.5) * _model.w_y;
+                draw_glyph(&draw, _model.glyph.as_ref(), &_model.rgbas, STROKE_WEIGHT, val, pt2(x, y), vec2(_model.w_x, _model.w_y));
+            }
         }
     }
     draw.to_frame(app, &frame).unwrap();
@@ -232,6 +187,15 @@ fn captured_frame_path(app: &App, frame: &Frame, offset: usize) -> std::path::Pa
         .with_extension("png")
 }
 
+fn observables_path(app: &App, name: &str) -> std::path::PathBuf {
+    // Write the observables next to the captured frames.
+    app.project_path()
+        .expect("failed to locate `project_path`")
+        .join(app.exe_name().unwrap())
+        .join(name)
+        .with_extension("csv")
+}
+
 fn main() {
     nannou::app(model).update(update).run();
 }
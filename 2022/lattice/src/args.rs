@@ -0,0 +1,29 @@
+//! Launch-time options, read from `--name value` or `--name=value` arguments.
+
+use std::env;
+use std::str::FromStr;
+
+/// Parsed value of `--name`, or `None` if it wasn't passed.
+///
+/// Panics if the value doesn't parse, so a typo fails at launch rather than
+/// silently falling back to the default.
+pub fn arg<T: FromStr>(name: &str) -> Option<T> {
+    let flag = format!("--{}", name);
+    let prefix = format!("--{}=", name);
+    let mut args = env::args().skip(1);
+    while let Some(a) = args.next() {
+        let value = if a == flag {
+            args.next()
+        } else {
+            a.strip_prefix(&prefix).map(String::from)
+        };
+        if let Some(value) = value {
+            return Some(
+                value
+                    .parse()
+                    .unwrap_or_else(|_| panic!("invalid value for --{}: {:?}", name, value)),
+            );
+        }
+    }
+    None
+}
@@ -0,0 +1,400 @@
+//! Per-site beta built up from layers, for the sketches that heat or cool
+//! parts of the lattice.
+//!
+//! A layer gives every site a beta: a constant, a distance-based hot or cold
+//! patch around points, paths or glyph outlines, an image mask, the inside
+//! of filled contours, or seeded noise. Layers combine with `Blend`, so a
+//! moving hotspot over a noisy background is one expression evaluated each
+//! step.
+//!
+//! Layers work in the sketch's drawing coordinates; `Grid` places site
+//! `(i, j)` at `(x0 + i w_x, y0 + j w_y)`.
+
+use ndarray::prelude::*;
+use ndarray::{Array, Ix2, Zip};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::field::average_rgb;
+use crate::outline::fill_mask;
+
+/// Where the lattice's sites sit in drawing coordinates.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Grid {
+    pub n_x: usize,
+    pub n_y: usize,
+    pub x0: f32,
+    pub y0: f32,
+    pub w_x: f32,
+    pub w_y: f32,
+}
+
+impl Grid {
+    pub fn position(&self, i: usize, j: usize) -> [f32; 2] {
+        [self.x0 + i as f32 * self.w_x, self.y0 + j as f32 * self.w_y]
+    }
+}
+
+/// Shapes a distance layer measures from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Points(Vec<[f32; 2]>),
+    /// An open polyline through its points.
+    Path(Vec<[f32; 2]>),
+    /// Loose line segments, as a glyph outline comes out of the text layout.
+    Outline(Vec<[[f32; 2]; 2]>),
+}
+
+impl Source {
+    /// Distance from `p` to the nearest part of the source, or `f32::MAX` if it is empty.
+    pub fn distance(&self, p: [f32; 2]) -> f32 {
+        match self {
+            Source::Points(points) => points.iter().map(|&q| length(sub(p, q))).fold(f32::MAX, f32::min),
+            Source::Path(points) if points.len() == 1 => length(sub(p, points[0])),
+            Source::Path(points) => points
+                .windows(2)
+                .map(|w| segment_distance(p, w[0], w[1]))
+                .fold(f32::MAX, f32::min),
+            Source::Outline(segments) => segments
+                .iter()
+                .map(|&[a, b]| segment_distance(p, a, b))
+                .fold(f32::MAX, f32::min),
+        }
+    }
+
+    /// Distance from every site of `grid` to the source, capped at `radius`.
+    ///
+    /// Points go through a distance transform: each row of sites takes the
+    /// lower envelope of one parabola per point within `radius` of the row,
+    /// so the work grows with the number of sites plus the number of points
+    /// times the rows they reach, never with their product. Paths and
+    /// outlines are measured site by site.
+    pub fn clamped_distances(&self, grid: &Grid, radius: f32) -> Array<f32, Ix2> {
+        match self {
+            Source::Points(points) => point_distances(points, grid, radius),
+            _ => {
+                let mut r = Array::<f32, Ix2>::zeros((grid.n_x, grid.n_y).f());
+                for i in 0..grid.n_x {
+                    for j in 0..grid.n_y {
+                        r[[i, j]] = self.distance(grid.position(i, j)).min(radius);
+                    }
+                }
+                r
+            }
+        }
+    }
+}
+
+/// Parabola `(x - q)^2 + h` of one point seen from one row, `h` being the
+/// squared distance between the point and the row.
+#[derive(Copy, Clone)]
+struct Parabola {
+    q: f32,
+    h: f32,
+}
+
+impl Parabola {
+    /// Where `self` starts to lie below `left`, which has the smaller `q`.
+    fn crossing(&self, left: &Parabola) -> f32 {
+        // in double precision, as q^2 swamps h when points crowd together
+        let (q, h) = (self.q as f64, self.h as f64);
+        let (left_q, left_h) = (left.q as f64, left.h as f64);
+        (((h + q * q) - (left_h + left_q * left_q)) / (2.0 * (q - left_q))) as f32
+    }
+}
+
+fn point_distances(points: &[[f32; 2]], grid: &Grid, radius: f32) -> Array<f32, Ix2> {
+    // hand each point to the rows it can reach
+    let mut rows: Vec<Vec<Parabola>> = vec![Vec::new(); grid.n_y];
+    for &[x, y] in points.iter() {
+        let j0 = ((y - radius - grid.y0) / grid.w_y).ceil().max(0.0) as usize;
+        let j1 = ((y + radius - grid.y0) / grid.w_y).floor().min(grid.n_y as f32 - 1.0);
+        if j1 < 0.0 {
+            continue;
+        }
+        for (j, row) in rows.iter_mut().enumerate().take(j1 as usize + 1).skip(j0) {
+            let dy = grid.y0 + j as f32 * grid.w_y - y;
+            row.push(Parabola { q: x, h: dy * dy });
+        }
+    }
+
+    let mut r = Array::<f32, Ix2>::from_elem((grid.n_x, grid.n_y).f(), radius);
+    let mut envelope: Vec<Parabola> = Vec::new();
+    let mut starts: Vec<f32> = Vec::new();
+    for (j, row) in rows.iter_mut().enumerate() {
+        if row.is_empty() {
+            continue;
+        }
+        row.sort_by(|a, b| a.q.total_cmp(&b.q));
+
+        // lower envelope, each parabola lowest from its start to the next one's
+        envelope.clear();
+        starts.clear();
+        for &p in row.iter() {
+            while let Some(last) = envelope.last() {
+                if p.q == last.q {
+                    if p.h < last.h {
+                        envelope.pop();
+                        starts.pop();
+                        continue;
+                    }
+                    break;
+                }
+                let s = p.crossing(last);
+                if s <= starts[starts.len() - 1] {
+                    envelope.pop();
+                    starts.pop();
+                    continue;
+                }
+                envelope.push(p);
+                starts.push(s);
+                break;
+            }
+            if envelope.is_empty() {
+                envelope.push(p);
+                starts.push(f32::NEG_INFINITY);
+            }
+        }
+
+        // read it off along the row
+        let mut k = 0;
+        for i in 0..grid.n_x {
+            let x = grid.x0 + i as f32 * grid.w_x;
+            while k + 1 < envelope.len() && starts[k + 1] <= x {
+                k += 1;
+            }
+            let dx = x - envelope[k].q;
+            r[[i, j]] = (dx * dx + envelope[k].h).sqrt().min(radius);
+        }
+    }
+    r
+}
+
+/// `n_points` evenly spaced round a circle, the first at angle `phase`.
+pub fn ring(center: [f32; 2], radius: f32, n_points: usize, phase: f32) -> Vec<[f32; 2]> {
+    (0..n_points)
+        .map(|k| {
+            let theta = phase + 2.0 * std::f32::consts::PI * k as f32 / n_points as f32;
+            [center[0] + radius * theta.cos(), center[1] + radius * theta.sin()]
+        })
+        .collect()
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn length(v: [f32; 2]) -> f32 {
+    (v[0] * v[0] + v[1] * v[1]).sqrt()
+}
+
+fn segment_distance(p: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    let (ab, ap) = (sub(b, a), sub(p, a));
+    let length_2 = ab[0] * ab[0] + ab[1] * ab[1];
+    if length_2 == 0.0 {
+        return length(ap);
+    }
+    let s = ((ap[0] * ab[0] + ap[1] * ab[1]) / length_2).clamp(0.0, 1.0);
+    length(sub(ap, [s * ab[0], s * ab[1]]))
+}
+
+/// How much of a distance layer's `inside` beta is left at a fraction `t`
+/// of its radius, from 1 at the source to 0 at the radius.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Falloff {
+    Linear,
+    /// Smoothstep, flat at both ends.
+    Smooth,
+    /// Falling fast near the source and flattening out.
+    Quadratic,
+    /// All of `inside` up to the radius and none beyond.
+    Step,
+}
+
+impl Falloff {
+    pub fn weight(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Falloff::Linear => 1.0 - t,
+            Falloff::Smooth => 1.0 - t * t * (3.0 - 2.0 * t),
+            Falloff::Quadratic => (1.0 - t) * (1.0 - t),
+            Falloff::Step => {
+                if t < 1.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Falloff {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Falloff::Linear),
+            "smooth" => Ok(Falloff::Smooth),
+            "quadratic" => Ok(Falloff::Quadratic),
+            "step" => Ok(Falloff::Step),
+            _ => Err(format!("unknown falloff {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Falloff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Falloff::Linear => "linear",
+            Falloff::Smooth => "smooth",
+            Falloff::Quadratic => "quadratic",
+            Falloff::Step => "step",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How the layers of a `BetaField::Blend` combine, site by site.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Blend {
+    /// The hottest layer wins.
+    Min,
+    /// The coldest layer wins.
+    Max,
+    Add,
+    Multiply,
+}
+
+impl Blend {
+    fn combine(&self, a: f32, b: f32) -> f32 {
+        match self {
+            Blend::Min => a.min(b),
+            Blend::Max => a.max(b),
+            Blend::Add => a + b,
+            Blend::Multiply => a * b,
+        }
+    }
+}
+
+impl FromStr for Blend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "min" => Ok(Blend::Min),
+            "max" => Ok(Blend::Max),
+            "add" => Ok(Blend::Add),
+            "multiply" => Ok(Blend::Multiply),
+            _ => Err(format!("unknown blend {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Blend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Blend::Min => "min",
+            Blend::Max => "max",
+            Blend::Add => "add",
+            Blend::Multiply => "multiply",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum BetaField {
+    Constant(f32),
+    /// `inside` at the source, handing over to `outside` along `falloff`
+    /// until `radius` away, and `outside` from there on.
+    Distance { source: Source, radius: f32, falloff: Falloff, inside: f32, outside: f32 },
+    /// `inside` where the weight is 1 and `outside` where it is 0, one weight per site.
+    Mask { weight: Array<f32, Ix2>, inside: f32, outside: f32 },
+    /// Smooth value noise between `low` and `high`, with features about
+    /// `scale` apart, the same for the same seed.
+    Noise { seed: u64, scale: f32, low: f32, high: f32 },
+    /// The layers folded together in order with one blend.
+    Blend(Blend, Vec<BetaField>),
+}
+
+impl BetaField {
+    /// A mask weighted by the luminance of an RGBA image, so bright parts
+    /// get `inside`, sampled onto the lattice as `ExternalField` does.
+    pub fn luminance_mask(width: usize, height: usize, rgba: &[u8], grid: &Grid, inside: f32, outside: f32) -> Self {
+        let mut weight = Array::<f32, Ix2>::zeros((grid.n_x, grid.n_y).f());
+        for i in 0..grid.n_x {
+            for j in 0..grid.n_y {
+                let [r, g, b] = average_rgb(width, height, rgba, grid.n_x, grid.n_y, i, j);
+                weight[[i, j]] = (0.2126 * r + 0.7152 * g + 0.0722 * b) / 255.0;
+            }
+        }
+        BetaField::Mask { weight, inside, outside }
+    }
+
+    /// `inside` within closed contours and `outside` beyond them, with holes
+    /// left by the nonzero winding rule.
+    pub fn filled(contours: &[Vec<[f32; 2]>], grid: &Grid, inside: f32, outside: f32) -> Self {
+        BetaField::Mask { weight: fill_mask(contours, grid), inside, outside }
+    }
+
+    /// Beta at every site of `grid`.
+    pub fn evaluate(&self, grid: &Grid) -> Array<f32, Ix2> {
+        match self {
+            BetaField::Constant(beta) => Array::<f32, Ix2>::from_elem((grid.n_x, grid.n_y).f(), *beta),
+            BetaField::Distance { source, radius, falloff, inside, outside } => {
+                let r = source.clamped_distances(grid, *radius);
+                r.mapv(|r| outside + (inside - outside) * falloff.weight(r / radius))
+            }
+            BetaField::Mask { weight, inside, outside } => {
+                assert_eq!(weight.dim(), (grid.n_x, grid.n_y), "mask doesn't match the lattice");
+                weight.mapv(|w| outside + (inside - outside) * w)
+            }
+            BetaField::Noise { seed, scale, low, high } => {
+                let mut beta = Array::<f32, Ix2>::zeros((grid.n_x, grid.n_y).f());
+                for i in 0..grid.n_x {
+                    for j in 0..grid.n_y {
+                        let [x, y] = grid.position(i, j);
+                        beta[[i, j]] = low + (high - low) * value_noise(*seed, x / scale, y / scale);
+                    }
+                }
+                beta
+            }
+            BetaField::Blend(blend, layers) => {
+                let mut layers = layers.iter();
+                let mut beta = match layers.next() {
+                    Some(layer) => layer.evaluate(grid),
+                    None => return BetaField::Constant(0.0).evaluate(grid),
+                };
+                for layer in layers {
+                    Zip::from(&mut beta)
+                        .and(&layer.evaluate(grid))
+                        .for_each(|a, &b| *a = blend.combine(*a, b));
+                }
+                beta
+            }
+        }
+    }
+}
+
+/// Noise in `[0, 1)`, interpolated smoothly between random values at the
+/// integer points.
+fn value_noise(seed: u64, x: f32, y: f32) -> f32 {
+    let (ix, iy) = (x.floor(), y.floor());
+    let (fx, fy) = (x - ix, y - iy);
+    let (sx, sy) = (fx * fx * (3.0 - 2.0 * fx), fy * fy * (3.0 - 2.0 * fy));
+    let (ix, iy) = (ix as i64, iy as i64);
+    let top = corner_value(seed, ix, iy) * (1.0 - sx) + corner_value(seed, ix + 1, iy) * sx;
+    let bottom = corner_value(seed, ix, iy + 1) * (1.0 - sx) + corner_value(seed, ix + 1, iy + 1) * sx;
+    top * (1.0 - sy) + bottom * sy
+}
+
+/// A hash of the seed and the integer point, as a number in `[0, 1)`.
+fn corner_value(seed: u64, ix: i64, iy: i64) -> f32 {
+    // splitmix64 finaliser over the mixed coordinates
+    let mut z = seed ^ (ix as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ (iy as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 40) as f32 / (1u64 << 24) as f32
+}
@@ -0,0 +1,77 @@
+//! What happens to bonds that run off the edge of the lattice.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Boundary {
+    /// Each edge wraps to the opposite one.
+    Periodic,
+    /// Bonds off the edge are dropped, so edge sites have fewer neighbours.
+    Open,
+    /// Bonds off the edge connect to a border held in the given state.
+    Fixed(i8),
+    /// Sites are wrapped as one long row: stepping off the right end of a row
+    /// lands on the start of the next. Top and bottom wrap as in `Periodic`.
+    Helical,
+}
+
+impl Boundary {
+    /// Site reached from `(x, y)`, which may lie one step outside the lattice,
+    /// or `None` if that step leaves through an open or fixed edge.
+    pub fn resolve(&self, x: isize, y: isize, n_x: usize, n_y: usize) -> Option<(usize, usize)> {
+        let (w, h) = (n_x as isize, n_y as isize);
+        match self {
+            Boundary::Periodic => Some((x.rem_euclid(w) as usize, y.rem_euclid(h) as usize)),
+            Boundary::Open | Boundary::Fixed(_) => {
+                if 0 <= x && x < w && 0 <= y && y < h {
+                    Some((x as usize, y as usize))
+                } else {
+                    None
+                }
+            }
+            Boundary::Helical => {
+                let k = (y * w + x).rem_euclid(w * h);
+                Some(((k % w) as usize, (k / w) as usize))
+            }
+        }
+    }
+
+    /// State of the border, if it has one.
+    pub fn fixed_state(&self) -> Option<i8> {
+        match self {
+            Boundary::Fixed(state) => Some(*state),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Boundary {
+    type Err = String;
+
+    /// `periodic`, `open`, `helical`, or `fixed` with an optional state as in `fixed:2`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "periodic" => Ok(Boundary::Periodic),
+            "open" => Ok(Boundary::Open),
+            "helical" => Ok(Boundary::Helical),
+            "fixed" => Ok(Boundary::Fixed(0)),
+            _ => match s.strip_prefix("fixed:").map(str::parse::<i8>) {
+                Some(Ok(state)) if state >= 0 => Ok(Boundary::Fixed(state)),
+                _ => Err(format!("unknown boundary {:?}", s)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Boundary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Boundary::Periodic => write!(f, "periodic"),
+            Boundary::Open => write!(f, "open"),
+            Boundary::Fixed(state) => write!(f, "fixed:{}", state),
+            Boundary::Helical => write!(f, "helical"),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::thread;
+
+use crate::boundary::Boundary;
+use crate::model::LatticeModel;
+use crate::random::{thread_streams, RandomSource};
+
+impl LatticeModel {
+    /// Metropolis over every site, one checkerboard colour at a time.
+    ///
+    /// Sites of one colour only neighbour sites of the other, so each half is
+    /// split into column bands and proposed in parallel against the current
+    /// lattice. Thread `k` draws from PCG stream `k` of `seed`, so a run is
+    /// reproducible for a fixed `n_threads`. Needs a bipartite topology without
+    /// diagonal couplings, a boundary other than helical, and even `n_x` and
+    /// `n_y` for the colouring to hold across a periodic seam.
+    pub fn checkerboard_sweep(&mut self) {
+        self.check_checkerboard();
+        let n_threads = self.n_threads.max(1).min(self.n_y);
+        let mut rngs = thread_streams(&mut self.thread_rngs, self.seed, n_threads);
+
+        for colour in 0..2 {
+            let this = &*self;
+            let bands: Vec<Vec<i8>> = thread::scope(|s| {
+                let handles: Vec<_> = rngs
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(k, rng)| {
+                        let j0 = k * this.n_y / n_threads;
+                        let j1 = (k + 1) * this.n_y / n_threads;
+                        s.spawn(move || this.propose_band(colour, j0, j1, rng))
+                    })
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            });
+
+            for (k, band) in bands.iter().enumerate() {
+                let j0 = k * self.n_y / n_threads;
+                let j1 = (k + 1) * self.n_y / n_threads;
+                self.write_band(colour, j0, j1, band);
+            }
+        }
+
+        self.thread_rngs = rngs;
+    }
+
+    /// `checkerboard_sweep` drawing from `source`, one band after another on
+    /// this thread, since a source can't be split into streams.
+    pub fn checkerboard_sweep_with<R: RandomSource + ?Sized>(&mut self, source: &mut R) {
+        self.check_checkerboard();
+        for colour in 0..2 {
+            let band = self.propose_band(colour, 0, self.n_y, source);
+            self.write_band(colour, 0, self.n_y, &band);
+        }
+    }
+
+    fn check_checkerboard(&self) {
+        assert!(self.topology.is_bipartite(), "checkerboard sweeps need a bipartite topology, not {}", self.topology);
+        assert!(self.couplings.j_diag == 0.0, "checkerboard sweeps can't split diagonal couplings");
+        assert!(self.boundary != Boundary::Helical, "checkerboard sweeps need a boundary other than helical");
+        assert!(
+            self.boundary != Boundary::Periodic || (self.n_x.is_multiple_of(2) && self.n_y.is_multiple_of(2)),
+            "periodic checkerboard sweeps need even sides, not {} by {}",
+            self.n_x,
+            self.n_y
+        );
+    }
+
+    /// Metropolis outcome for every site of `colour` in columns `j0..j1`.
+    fn propose_band<R: RandomSource + ?Sized>(&self, colour: usize, j0: usize, j1: usize, rng: &mut R) -> Vec<i8> {
+        let mut states = Vec::with_capacity((j1 - j0) * self.n_x / 2 + 1);
+        for j in j0..j1 {
+            for i in ((j + colour) % 2..self.n_x).step_by(2) {
+                let old_aij = self.a[[i, j]];
+                let new_aij = rng.next_index(0..self.n_states) as i8;
+                let old_energy = self.site_energy(i, j, old_aij);
+                let new_energy = self.site_energy(i, j, new_aij);
+
+                // flip a coin and reject if condition is met
+                if rng.next_f32().ln() > self.beta.at(i, j) * (old_energy - new_energy) {
+                    states.push(old_aij);
+                } else {
+                    states.push(new_aij);
+                }
+            }
+        }
+        states
+    }
+
+    /// Put the states from `propose_band` back, in the order it visited them.
+    fn write_band(&mut self, colour: usize, j0: usize, j1: usize, band: &[i8]) {
+        let mut states = band.iter();
+        for j in j0..j1 {
+            for i in ((j + colour) % 2..self.n_x).step_by(2) {
+                self.a[[i, j]] = *states.next().unwrap();
+            }
+        }
+    }
+}
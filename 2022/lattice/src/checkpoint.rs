@@ -0,0 +1,38 @@
+//! Snapshots of a whole run, so a sketch can skip its equilibration steps.
+//!
+//! A checkpoint holds the lattice with every RNG stream it draws from, the
+//! sketch's step counter, and whatever else the sketch evolves (hotspots and
+//! the like). Restoring one and stepping on with the same options gives the
+//! same frames, bit for bit, as the run that saved it.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use crate::model::LatticeModel;
+
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint<S> {
+    pub step: usize,
+    pub lattice: LatticeModel,
+    /// State the sketch keeps outside the lattice.
+    pub sketch: S,
+}
+
+impl<S: Serialize + DeserializeOwned> Checkpoint<S> {
+    /// Write to `path`, making its directory if needed.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let writer = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(writer, self).map_err(io::Error::other)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        bincode::deserialize_from(reader).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
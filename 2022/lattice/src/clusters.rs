@@ -0,0 +1,316 @@
+//! Domains as objects: the connected regions of one state in a lattice.
+//!
+//! Sites join a cluster when they share a state and are nearest neighbours
+//! in the lattice's topology, whatever the couplings: a zero coupling doesn't
+//! cut a region apart and diagonal couplings don't join sites that only
+//! touch at a corner. Clusters wrap across periodic edges and stop at open
+//! or fixed ones. On the square lattice these are the regions `DomainMap`
+//! outlines, except that it cuts them at a periodic seam.
+//!
+//! Each cluster carries an id that outlives a single labelling: `update`
+//! hands every new cluster the id of the old cluster it overlaps most, so a
+//! domain keeps its id, and its colour, while it drifts, grows or splits,
+//! and only clusters with no past get fresh ids.
+
+use ndarray::{Array, Ix2, ShapeBuilder};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::model::LatticeModel;
+use crate::palette::{gradient, hue};
+use crate::swendsen_wang::find;
+
+/// How a sketch colours its sites.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Colouring {
+    /// The palette colour of the site's state.
+    State,
+    /// Along the palette by the size of the site's cluster, on a log scale
+    /// from one site to the whole lattice.
+    Size,
+    /// A hue picked at random for the site's cluster, kept while it lives.
+    Hue,
+}
+
+impl FromStr for Colouring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "state" => Ok(Colouring::State),
+            "size" => Ok(Colouring::Size),
+            "hue" => Ok(Colouring::Hue),
+            _ => Err(format!("unknown colouring {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Colouring {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Colouring::State => write!(f, "state"),
+            Colouring::Size => write!(f, "size"),
+            Colouring::Hue => write!(f, "hue"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cluster {
+    pub id: u64,
+    pub state: i8,
+    pub size: usize,
+}
+
+impl Cluster {
+    /// A fully saturated hue that depends only on the id.
+    pub fn hue(&self) -> [u8; 4] {
+        // splitmix64's finaliser, so neighbouring ids land far apart
+        let mut z = self.id.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        hue(2.0 * std::f32::consts::PI * (z >> 40) as f32 / (1u64 << 24) as f32)
+    }
+}
+
+/// Every cluster of a lattice, and which one each site belongs to.
+pub struct Clusters {
+    pub n_x: usize,
+    pub n_y: usize,
+    /// Index into `clusters` of each site's cluster.
+    pub labels: Array<usize, Ix2>,
+    pub clusters: Vec<Cluster>,
+    next_id: u64,
+}
+
+impl Clusters {
+    /// Clusters of `lattice`, with ids counting up from zero.
+    pub fn label(lattice: &LatticeModel) -> Self {
+        let (labels, clusters) = label(lattice);
+        let next_id = clusters.len() as u64;
+        let mut clusters = Clusters { n_x: lattice.n_x, n_y: lattice.n_y, labels, clusters, next_id };
+        for (k, cluster) in clusters.clusters.iter_mut().enumerate() {
+            cluster.id = k as u64;
+        }
+        clusters
+    }
+
+    /// Label `lattice` afresh, carrying ids over from the clusters it had
+    /// before. New and old clusters are paired off greedily by how many
+    /// sites they share in the same state, largest overlap first, so when a
+    /// cluster splits its largest piece keeps the id, and when clusters
+    /// merge the result takes the id of the one it overlaps most.
+    pub fn update(&mut self, lattice: &LatticeModel) {
+        assert_eq!((lattice.n_x, lattice.n_y), (self.n_x, self.n_y), "lattice changed size");
+        let (labels, mut clusters) = label(lattice);
+
+        let mut overlaps: HashMap<(usize, usize), usize> = HashMap::new();
+        for (&new, &old) in labels.iter().zip(self.labels.iter()) {
+            if clusters[new].state == self.clusters[old].state {
+                *overlaps.entry((new, old)).or_insert(0) += 1;
+            }
+        }
+        let mut overlaps: Vec<((usize, usize), usize)> = overlaps.into_iter().collect();
+        overlaps.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let mut matched = vec![false; clusters.len()];
+        let mut taken = vec![false; self.clusters.len()];
+        for ((new, old), _) in overlaps {
+            if !matched[new] && !taken[old] {
+                clusters[new].id = self.clusters[old].id;
+                matched[new] = true;
+                taken[old] = true;
+            }
+        }
+        for (cluster, _) in clusters.iter_mut().zip(matched).filter(|(_, matched)| !matched) {
+            cluster.id = self.next_id;
+            self.next_id += 1;
+        }
+
+        self.labels = labels;
+        self.clusters = clusters;
+    }
+
+    /// The cluster site `(i, j)` belongs to.
+    pub fn at(&self, i: usize, j: usize) -> &Cluster {
+        &self.clusters[self.labels[[i, j]]]
+    }
+
+    /// Colour of site `(i, j)` by the size of its cluster, along `colours`.
+    pub fn size_colour(&self, i: usize, j: usize, colours: &[[u8; 4]]) -> [u8; 4] {
+        let n_sites = (self.n_x * self.n_y) as f32;
+        let t = if n_sites > 1.0 { (self.at(i, j).size as f32).ln() / n_sites.ln() } else { 1.0 };
+        gradient(colours, t)
+    }
+
+    /// Number of clusters of each size, as `(size, count)` from the smallest.
+    pub fn histogram(&self) -> Vec<(usize, usize)> {
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        for cluster in self.clusters.iter() {
+            *counts.entry(cluster.size).or_insert(0) += 1;
+        }
+        let mut histogram: Vec<(usize, usize)> = counts.into_iter().collect();
+        histogram.sort_unstable();
+        histogram
+    }
+}
+
+/// Union-find over nearest neighbours in equal states, then each root numbered
+/// in the order it's first met. The clusters' ids are left for the caller.
+fn label(lattice: &LatticeModel) -> (Array<usize, Ix2>, Vec<Cluster>) {
+    let (n_x, n_y) = (lattice.n_x, lattice.n_y);
+    let index = |i: usize, j: usize| i + j * n_x;
+    let mut parent: Vec<usize> = (0..n_x * n_y).collect();
+    for j in 0..n_y {
+        for i in 0..n_x {
+            let aij = lattice.a[[i, j]];
+            for &(di, dj) in lattice.topology.offsets(i, j).iter() {
+                let (x, y) = (i as isize + di, j as isize + dj);
+                match lattice.boundary.resolve(x, y, n_x, n_y) {
+                    Some((ni, nj)) if lattice.a[[ni, nj]] == aij => {
+                        let root = find(&mut parent, index(i, j));
+                        let other = find(&mut parent, index(ni, nj));
+                        parent[other] = root;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let mut labels = Array::<usize, Ix2>::zeros((n_x, n_y).f());
+    let mut roots: Vec<usize> = vec![usize::MAX; n_x * n_y];
+    let mut clusters: Vec<Cluster> = Vec::new();
+    for j in 0..n_y {
+        for i in 0..n_x {
+            let root = find(&mut parent, index(i, j));
+            if roots[root] == usize::MAX {
+                roots[root] = clusters.len();
+                clusters.push(Cluster { id: 0, state: lattice.a[[i, j]], size: 0 });
+            }
+            labels[[i, j]] = roots[root];
+            clusters[roots[root]].size += 1;
+        }
+    }
+    (labels, clusters)
+}
+
+/// Writes `step,size,count` per update, a row for each cluster size present.
+pub struct ClusterRecorder {
+    writer: BufWriter<File>,
+    step: usize,
+}
+
+impl ClusterRecorder {
+    /// Create (or truncate) the CSV at `path`, making its directory if needed.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "step,size,count")?;
+        Ok(ClusterRecorder { writer, step: 0 })
+    }
+
+    /// Append the histogram of `clusters`. Call once after every update.
+    pub fn record(&mut self, clusters: &Clusters) -> io::Result<()> {
+        for (size, count) in clusters.histogram() {
+            writeln!(self.writer, "{},{},{}", self.step, size, count)?;
+        }
+        self.writer.flush()?;
+        self.step += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary::Boundary;
+    use crate::model::{Beta, Couplings};
+
+    /// A two-state lattice from rows listed top to bottom.
+    fn lattice(rows: &[&str], boundary: Boundary) -> LatticeModel {
+        let (n_x, n_y) = (rows[0].len(), rows.len());
+        let mut lattice = LatticeModel::new(n_x, n_y, 2, Beta::Global(0.0), 0);
+        lattice.boundary = boundary;
+        relabel(&mut lattice, rows);
+        lattice
+    }
+
+    fn relabel(lattice: &mut LatticeModel, rows: &[&str]) {
+        for (r, row) in rows.iter().enumerate() {
+            for (i, c) in row.bytes().enumerate() {
+                lattice.a[[i, lattice.n_y - 1 - r]] = (c - b'0') as i8;
+            }
+        }
+    }
+
+    /// Ids of the clusters in `state`, largest first.
+    fn ids(clusters: &Clusters, state: i8) -> Vec<(usize, u64)> {
+        let mut ids: Vec<(usize, u64)> =
+            clusters.clusters.iter().filter(|c| c.state == state).map(|c| (c.size, c.id)).collect();
+        ids.sort_unstable_by(|a, b| b.cmp(a));
+        ids
+    }
+
+    #[test]
+    fn clusters_wrap_only_across_periodic_edges() {
+        let rows = ["1001", "1001", "0000"];
+        assert_eq!(ids(&Clusters::label(&lattice(&rows, Boundary::Periodic)), 1).len(), 1);
+        assert_eq!(ids(&Clusters::label(&lattice(&rows, Boundary::Open)), 1).len(), 2);
+    }
+
+    #[test]
+    fn clusters_ignore_the_couplings() {
+        let rows = ["10000", "01000", "00000", "00110"];
+        let mut lattice = lattice(&rows, Boundary::Open);
+        let sizes = |lattice: &LatticeModel| -> Vec<usize> {
+            ids(&Clusters::label(lattice), 1).iter().map(|&(size, _)| size).collect()
+        };
+        assert_eq!(sizes(&lattice), vec![2, 1, 1]);
+        lattice.couplings = Couplings { j_x: 0.0, j_y: 1.0, j_diag: 1.0 };
+        assert_eq!(sizes(&lattice), vec![2, 1, 1]);
+    }
+
+    #[test]
+    fn drifting_clusters_keep_their_ids() {
+        let mut lattice = lattice(&["0000", "0110", "0110", "0000"], Boundary::Periodic);
+        let mut clusters = Clusters::label(&lattice);
+        let before = (ids(&clusters, 0), ids(&clusters, 1));
+        relabel(&mut lattice, &["0000", "0011", "0011", "0000"]);
+        clusters.update(&lattice);
+        assert_eq!((ids(&clusters, 0), ids(&clusters, 1)), before);
+    }
+
+    #[test]
+    fn the_largest_piece_of_a_split_keeps_the_id() {
+        let mut lattice = lattice(&["0000000", "0111110", "0000000"], Boundary::Open);
+        let mut clusters = Clusters::label(&lattice);
+        let (background, row) = (ids(&clusters, 0)[0].1, ids(&clusters, 1)[0].1);
+        relabel(&mut lattice, &["0000000", "0111010", "0000000"]);
+        clusters.update(&lattice);
+        assert_eq!(ids(&clusters, 0), vec![(17, background)]);
+        assert_eq!(ids(&clusters, 1), vec![(3, row), (1, 2)]);
+    }
+
+    #[test]
+    fn merged_clusters_take_the_id_they_overlap_most() {
+        let mut lattice = lattice(&["0000000", "0111010", "0000000"], Boundary::Open);
+        let mut clusters = Clusters::label(&lattice);
+        let pieces = ids(&clusters, 1);
+        relabel(&mut lattice, &["0000000", "0111110", "0000000"]);
+        clusters.update(&lattice);
+        assert_eq!(ids(&clusters, 1), vec![(5, pieces[0].1)]);
+
+        // the smaller piece's id is retired, so a cluster born later gets a new one
+        relabel(&mut lattice, &["1000000", "0111110", "0000000"]);
+        clusters.update(&lattice);
+        assert_eq!(ids(&clusters, 1), vec![(5, pieces[0].1), (1, 3)]);
+    }
+}
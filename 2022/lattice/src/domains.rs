@@ -0,0 +1,336 @@
+//! The walls between Potts domains, traced as closed contours for vector
+//! rendering and export.
+//!
+//! Each state's domains are traced with marching squares over the site
+//! centres, on the mask of sites in that state, so a wall between two
+//! domains is traced once from either side. Contours run anticlockwise round
+//! a domain and clockwise round the holes in it, and everything outside the
+//! lattice counts as another state, so domains that wrap across a periodic
+//! seam are closed off along the edge. Domains are read on the square
+//! lattice whatever the model's topology or couplings, so sites touching
+//! only at a corner belong to separate domains, as they do in `Clusters`.
+//!
+//! Points are in cell units, with site `(i, j)` at `(i + 0.5, j + 0.5)`.
+
+use ndarray::{Array, Ix2};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::model::LatticeModel;
+
+/// How the staircase of a traced wall is rounded off.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Smoothing {
+    None,
+    /// Corner cutting, each round replacing every edge with points a quarter
+    /// and three quarters of the way along it.
+    Chaikin(usize),
+    /// A closed Catmull–Rom spline through the traced points, with this many
+    /// points per traced edge.
+    Spline(usize),
+}
+
+/// Rounds of corner cutting in a `chaikin` given without a count.
+pub const DEFAULT_CHAIKIN_ROUNDS: usize = 3;
+
+/// Points per edge of a `spline` given without a count.
+pub const DEFAULT_SPLINE_POINTS: usize = 4;
+
+impl Smoothing {
+    pub fn apply(&self, contour: &[[f32; 2]]) -> Vec<[f32; 2]> {
+        match *self {
+            Smoothing::None => contour.to_vec(),
+            Smoothing::Chaikin(n_rounds) => chaikin(contour, n_rounds),
+            Smoothing::Spline(n_points) => catmull_rom(contour, n_points),
+        }
+    }
+}
+
+impl FromStr for Smoothing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, count) = match s.split_once(':') {
+            Some((name, count)) => {
+                let count = count.parse().map_err(|_| format!("invalid smoothing count {:?}", count))?;
+                (name, Some(count))
+            }
+            None => (s, None),
+        };
+        match name {
+            "none" if count.is_none() => Ok(Smoothing::None),
+            "chaikin" => Ok(Smoothing::Chaikin(count.unwrap_or(DEFAULT_CHAIKIN_ROUNDS))),
+            "spline" => Ok(Smoothing::Spline(count.unwrap_or(DEFAULT_SPLINE_POINTS).max(1))),
+            _ => Err(format!("unknown smoothing {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Smoothing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Smoothing::None => write!(f, "none"),
+            Smoothing::Chaikin(n_rounds) => write!(f, "chaikin:{}", n_rounds),
+            Smoothing::Spline(n_points) => write!(f, "spline:{}", n_points),
+        }
+    }
+}
+
+/// Chaikin's corner cutting on a closed contour.
+pub fn chaikin(contour: &[[f32; 2]], n_rounds: usize) -> Vec<[f32; 2]> {
+    let mut points = contour.to_vec();
+    for _ in 0..n_rounds {
+        let n = points.len();
+        if n < 3 {
+            break;
+        }
+        let mut cut = Vec::with_capacity(2 * n);
+        for k in 0..n {
+            let (a, b) = (points[k], points[(k + 1) % n]);
+            cut.push([0.75 * a[0] + 0.25 * b[0], 0.75 * a[1] + 0.25 * b[1]]);
+            cut.push([0.25 * a[0] + 0.75 * b[0], 0.25 * a[1] + 0.75 * b[1]]);
+        }
+        points = cut;
+    }
+    points
+}
+
+/// `n_points` points per edge of a closed uniform Catmull–Rom spline through
+/// the contour's points.
+pub fn catmull_rom(contour: &[[f32; 2]], n_points: usize) -> Vec<[f32; 2]> {
+    let n = contour.len();
+    if n < 3 {
+        return contour.to_vec();
+    }
+    let mut points = Vec::with_capacity(n * n_points);
+    for k in 0..n {
+        let [p0, p1, p2, p3] = [contour[(k + n - 1) % n], contour[k], contour[(k + 1) % n], contour[(k + 2) % n]];
+        for m in 0..n_points {
+            let t = m as f32 / n_points as f32;
+            let (t2, t3) = (t * t, t * t * t);
+            let blend = |c: usize| {
+                0.5 * (2.0 * p1[c]
+                    + (p2[c] - p0[c]) * t
+                    + (2.0 * p0[c] - 5.0 * p1[c] + 4.0 * p2[c] - p3[c]) * t2
+                    + (3.0 * p1[c] - p0[c] - 3.0 * p2[c] + p3[c]) * t3)
+            };
+            points.push([blend(0), blend(1)]);
+        }
+    }
+    points
+}
+
+/// Contours round every domain of `state` in `a`, in the order their first
+/// crossings come in a scan of the lattice.
+pub fn trace(a: &Array<i8, Ix2>, state: i8) -> Vec<Vec<[f32; 2]>> {
+    let (n_x, n_y) = a.dim();
+    let inside = |i: isize, j: isize| {
+        i >= 0 && j >= 0 && (i as usize) < n_x && (j as usize) < n_y && a[[i as usize, j as usize]] == state
+    };
+
+    // where a wall crosses between two neighbouring site centres: the one at
+    // (i, j) and the one right of it, or above it for the odd indices
+    let n_row = n_x as isize + 2;
+    let crossing = |i: isize, j: isize, vertical: bool| (2 * ((j + 1) * n_row + i + 1)) as usize + vertical as usize;
+    let point = |k: usize| {
+        let (i, j) = ((k / 2) as isize % n_row - 1, (k / 2) as isize / n_row - 1);
+        if k % 2 == 1 {
+            [i as f32 + 0.5, j as f32 + 1.0]
+        } else {
+            [i as f32 + 1.0, j as f32 + 0.5]
+        }
+    };
+
+    // each square between four site centres adds a piece of wall from where
+    // the boundary leaves the domain to where it last came in, walking
+    // anticlockwise round the square, which keeps corner-touching sites apart
+    let mut next: Vec<Option<usize>> = vec![None; 2 * (n_x + 2) * (n_y + 2)];
+    for j in -1..n_y as isize {
+        for i in -1..n_x as isize {
+            let corners = [inside(i, j), inside(i + 1, j), inside(i + 1, j + 1), inside(i, j + 1)];
+            if corners.iter().all(|&c| c) || corners.iter().all(|&c| !c) {
+                continue;
+            }
+            // the square's sides, anticlockwise from the bottom
+            let sides = [crossing(i, j, false), crossing(i + 1, j, true), crossing(i, j + 1, false), crossing(i, j, true)];
+            for k in 0..4 {
+                if corners[k] && !corners[(k + 1) % 4] {
+                    let entry = (1..4)
+                        .map(|m| (k + 4 - m) % 4)
+                        .find(|&m| !corners[m] && corners[(m + 1) % 4])
+                        .unwrap();
+                    next[sides[k]] = Some(sides[entry]);
+                }
+            }
+        }
+    }
+
+    // follow the pieces round into loops, each piece taken once
+    let mut contours = Vec::new();
+    for start in 0..next.len() {
+        let mut contour = Vec::new();
+        let mut k = start;
+        while let Some(to) = next[k].take() {
+            contour.push(point(k));
+            k = to;
+        }
+        if !contour.is_empty() {
+            contours.push(contour);
+        }
+    }
+    contours
+}
+
+/// The domain walls of a whole lattice, by state.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DomainMap {
+    pub n_x: usize,
+    pub n_y: usize,
+    /// Contours round each state's domains, indexed by state.
+    pub contours: Vec<Vec<Vec<[f32; 2]>>>,
+}
+
+impl DomainMap {
+    pub fn trace(lattice: &LatticeModel) -> Self {
+        let contours = (0..lattice.n_states).map(|state| trace(&lattice.a, state as i8)).collect();
+        DomainMap { n_x: lattice.n_x, n_y: lattice.n_y, contours }
+    }
+
+    pub fn smoothed(&self, smoothing: Smoothing) -> Self {
+        let contours = self
+            .contours
+            .iter()
+            .map(|contours| contours.iter().map(|c| smoothing.apply(c)).collect())
+            .collect();
+        DomainMap { contours, ..*self }
+    }
+
+    /// An SVG document `scale` pixels per cell, with a group per state
+    /// filled in that state's colour and its walls stroked `stroke_width`
+    /// cells wide in black, or not at all for zero. The lattice's y runs up
+    /// the page.
+    pub fn to_svg(&self, colours: &[[u8; 4]], scale: f32, stroke_width: f32) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+            self.n_x as f32 * scale,
+            self.n_y as f32 * scale,
+            self.n_x,
+            self.n_y
+        );
+        let stroke = if stroke_width > 0.0 {
+            format!("stroke=\"#000000\" stroke-width=\"{}\" stroke-linejoin=\"round\"", stroke_width)
+        } else {
+            "stroke=\"none\"".to_string()
+        };
+        for (state, contours) in self.contours.iter().enumerate() {
+            if contours.is_empty() {
+                continue;
+            }
+            let c = colours[state % colours.len()];
+            let mut d = String::new();
+            for contour in contours.iter() {
+                for (k, p) in contour.iter().enumerate() {
+                    let command = if k == 0 { 'M' } else { 'L' };
+                    d.push_str(&format!("{}{:.3} {:.3} ", command, p[0], self.n_y as f32 - p[1]));
+                }
+                d.push_str("Z ");
+            }
+            svg.push_str(&format!(
+                "<g id=\"state-{}\" fill=\"#{:02X}{:02X}{:02X}\" fill-opacity=\"{:.3}\" fill-rule=\"evenodd\" {}>\n<path d=\"{}\"/>\n</g>\n",
+                state,
+                c[0],
+                c[1],
+                c[2],
+                c[3] as f32 / u8::MAX as f32,
+                stroke,
+                d.trim_end()
+            ));
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Write `to_svg` to `path`, making its directory if needed.
+    pub fn save_svg<P: AsRef<Path>>(&self, path: P, colours: &[[u8; 4]], scale: f32, stroke_width: f32) -> io::Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_svg(colours, scale, stroke_width))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::ShapeBuilder;
+
+    /// A lattice from rows listed top to bottom, as they'd be drawn.
+    fn lattice(rows: &[&str]) -> Array<i8, Ix2> {
+        let (n_x, n_y) = (rows[0].len(), rows.len());
+        let mut a = Array::<i8, Ix2>::zeros((n_x, n_y).f());
+        for (r, row) in rows.iter().enumerate() {
+            for (i, c) in row.bytes().enumerate() {
+                a[[i, n_y - 1 - r]] = (c - b'0') as i8;
+            }
+        }
+        a
+    }
+
+    /// Positive anticlockwise.
+    fn signed_area(contour: &[[f32; 2]]) -> f32 {
+        let n = contour.len();
+        (0..n)
+            .map(|k| {
+                let (p, q) = (contour[k], contour[(k + 1) % n]);
+                0.5 * (p[0] * q[1] - q[0] * p[1])
+            })
+            .sum()
+    }
+
+    #[test]
+    fn a_lone_site_is_a_diamond() {
+        let contours = trace(&lattice(&["000", "010", "000"]), 1);
+        assert_eq!(contours.len(), 1);
+        assert_eq!(contours[0].len(), 4);
+        assert_eq!(signed_area(&contours[0]), 0.5);
+    }
+
+    #[test]
+    fn saddles_keep_corner_touching_sites_apart() {
+        // both diagonals of the same square, for either state
+        for rows in [["10", "01"], ["01", "10"]].iter() {
+            let a = lattice(rows);
+            for state in 0..2 {
+                let contours = trace(&a, state);
+                assert_eq!(contours.len(), 2, "state {} of {:?}", state, rows);
+                for contour in contours.iter() {
+                    assert_eq!(contour.len(), 4);
+                    assert!(signed_area(contour) > 0.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn holes_run_clockwise() {
+        let contours = trace(&lattice(&["111", "101", "111"]), 1);
+        assert_eq!(contours.len(), 2);
+        let mut areas: Vec<f32> = contours.iter().map(|c| signed_area(c)).collect();
+        areas.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(areas, vec![-0.5, 8.5]);
+    }
+
+    #[test]
+    fn a_checkerboard_is_all_lone_sites() {
+        // a checkerboard, every square between four sites a saddle
+        let a = lattice(&["0101", "1010", "0101", "1010"]);
+        for state in 0..2 {
+            let contours = trace(&a, state);
+            assert_eq!(contours.len(), 8);
+            assert!(contours.iter().all(|c| c.len() == 4));
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! External field pulling each site toward a preferred state.
+//!
+//! A site in its preferred state gains `-h`, any other state costs `+h`, in
+//! the same units as the bonds. Fields can be read from an RGBA image, which
+//! is box-filtered down (or stretched up) to the lattice: column `i` and row
+//! `j` of the lattice sample the image from its top-left corner, as the
+//! texture sketches draw it.
+
+use ndarray::prelude::*;
+use ndarray::{Array, Ix2};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExternalField {
+    pub preferred: Array<i8, Ix2>,
+    /// Field strength per site. Cluster samplers need it non-negative.
+    pub h: Array<f32, Ix2>,
+}
+
+impl ExternalField {
+    /// The same preferred state and strength everywhere.
+    pub fn uniform(n_x: usize, n_y: usize, state: i8, h: f32) -> Self {
+        ExternalField {
+            preferred: Array::<i8, Ix2>::from_elem((n_x, n_y).f(), state),
+            h: Array::<f32, Ix2>::from_elem((n_x, n_y).f(), h),
+        }
+    }
+
+    /// Prefer the state whose band of luminance contains the pixel's, darkest
+    /// first, so two states follow black and white in `palette::MONO`.
+    pub fn from_luminance(
+        width: usize,
+        height: usize,
+        rgba: &[u8],
+        n_x: usize,
+        n_y: usize,
+        n_states: usize,
+        h: f32,
+    ) -> Self {
+        let mut field = ExternalField::uniform(n_x, n_y, 0, h);
+        for i in 0..n_x {
+            for j in 0..n_y {
+                let [r, g, b] = average_rgb(width, height, rgba, n_x, n_y, i, j);
+                let luminance = (0.2126 * r + 0.7152 * g + 0.0722 * b) / 255.0;
+                let state = (luminance * n_states as f32) as usize;
+                field.preferred[[i, j]] = state.min(n_states - 1) as i8;
+            }
+        }
+        field
+    }
+
+    /// Prefer the palette entry nearest the pixel's colour.
+    pub fn from_palette(
+        width: usize,
+        height: usize,
+        rgba: &[u8],
+        n_x: usize,
+        n_y: usize,
+        palette: &[[u8; 4]],
+        h: f32,
+    ) -> Self {
+        let mut field = ExternalField::uniform(n_x, n_y, 0, h);
+        for i in 0..n_x {
+            for j in 0..n_y {
+                let rgb = average_rgb(width, height, rgba, n_x, n_y, i, j);
+                let distance = |c: &[u8; 4]| -> f32 { (0..3).map(|k| (c[k] as f32 - rgb[k]).powi(2)).sum() };
+                let mut state = 0;
+                for (s, c) in palette.iter().enumerate() {
+                    if distance(c) < distance(&palette[state]) {
+                        state = s;
+                    }
+                }
+                field.preferred[[i, j]] = state as i8;
+            }
+        }
+        field
+    }
+
+    /// Field energy of site `(i, j)` in `state`.
+    pub fn energy(&self, i: usize, j: usize, state: i8) -> f32 {
+        if state == self.preferred[[i, j]] {
+            -self.h[[i, j]]
+        } else {
+            self.h[[i, j]]
+        }
+    }
+}
+
+/// Mean colour of the pixels under site `(i, j)`, or the nearest pixel when
+/// sites are smaller than pixels.
+pub(crate) fn average_rgb(width: usize, height: usize, rgba: &[u8], n_x: usize, n_y: usize, i: usize, j: usize) -> [f32; 3] {
+    assert_eq!(rgba.len(), 4 * width * height, "expected {} by {} RGBA pixels", width, height);
+    let x0 = i * width / n_x;
+    let y0 = j * height / n_y;
+    let x1 = ((i + 1) * width / n_x).max(x0 + 1);
+    let y1 = ((j + 1) * height / n_y).max(y0 + 1);
+    let mut sum = [0.0; 3];
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let p = 4 * (x + y * width);
+            for k in 0..3 {
+                sum[k] += rgba[p + k] as f32;
+            }
+        }
+    }
+    let n = ((x1 - x0) * (y1 - y0)) as f32;
+    [sum[0] / n, sum[1] / n, sum[2] / n]
+}
@@ -0,0 +1,266 @@
+//! What a lattice site looks like, as marks a sketch draws in the site's cell.
+//!
+//! A glyph turns a state into marks in cell units: the cell is one unit
+//! across, centred on the origin, with y up. The sketch scales them to its
+//! spin widths, moves them to the site and draws them in the palette colour
+//! each mark names. Marks may spill out of their cell, as the leaves do.
+
+use std::f32::consts::PI;
+use std::fs;
+use std::path::Path;
+
+use crate::outline::parse_svg_path;
+
+/// One thing to draw for a site.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mark {
+    /// A line through `points`, back to the first point if `closed`.
+    Stroke { points: Vec<[f32; 2]>, closed: bool, colour: usize },
+    /// A filled polygon.
+    Fill { points: Vec<[f32; 2]>, colour: usize },
+    /// A filled ellipse `size` across, turned `rotation` radians anticlockwise.
+    Ellipse { centre: [f32; 2], size: [f32; 2], rotation: f32, colour: usize },
+    /// Triangles, three vertices at a time, each vertex with its own colour.
+    /// The third coordinate points out of the screen.
+    Mesh { vertices: Vec<([f32; 3], usize)> },
+}
+
+/// How a site in `state` of `n_states` is drawn. Colours are palette
+/// indices, so a glyph works with any palette that has `n_states` colours.
+pub trait Glyph {
+    fn marks(&self, state: usize, n_states: usize) -> Vec<Mark>;
+}
+
+/// An outlined regular polygon, a different number of corners per state,
+/// with two corners drawing a line.
+pub struct Polygon;
+
+impl Glyph for Polygon {
+    fn marks(&self, state: usize, _n_states: usize) -> Vec<Mark> {
+        let n_points = match state {
+            0 => 2,
+            1 => 3,
+            2 => 5,
+            _ => state + 3,
+        };
+        let points = (0..n_points)
+            .map(|k| {
+                let theta = 2.0 * PI * k as f32 / n_points as f32;
+                [0.5 * theta.sin(), 0.5 * theta.cos()]
+            })
+            .collect();
+        vec![Mark::Stroke { points, closed: true, colour: state }]
+    }
+}
+
+/// A long, thin leaf several cells across, at an angle set by the state.
+pub struct Leaf;
+
+impl Glyph for Leaf {
+    fn marks(&self, state: usize, _n_states: usize) -> Vec<Mark> {
+        let (length, rotation) = match state {
+            0 => (4.0, PI / 2.5),
+            1 => (6.0, PI / 3.0),
+            2 => (8.0, PI / 4.5),
+            _ => (3.0, PI / 2.5),
+        };
+        vec![Mark::Ellipse { centre: [0.0, 0.0], size: [length, 0.2], rotation, colour: state }]
+    }
+}
+
+/// A cube half a cell wide, tumbled by an angle set by the state, with its
+/// far corner in the next colour of the palette.
+pub struct Cube;
+
+impl Glyph for Cube {
+    fn marks(&self, state: usize, n_states: usize) -> Vec<Mark> {
+        let angle = match state {
+            0 => PI / 2.5,
+            1 => PI / 3.0,
+            2 => PI / 4.0,
+            _ => PI / 2.5,
+        };
+        let (sin, cos) = angle.sin_cos();
+        let turn = |[x, y, z]: [f32; 3]| {
+            // about x, then y, then z, all by the same angle
+            let [x, y, z] = [x, cos * y - sin * z, sin * y + cos * z];
+            let [x, y, z] = [cos * x + sin * z, y, -sin * x + cos * z];
+            [cos * x - sin * y, sin * x + cos * y, z]
+        };
+        let mut vertices = Vec::with_capacity(36);
+        for axis in 0..3 {
+            for side in [-0.25, 0.25] {
+                // two triangles per face, with corners round the face in order
+                let corners: Vec<[f32; 3]> = [(-0.25, -0.25), (0.25, -0.25), (0.25, 0.25), (-0.25, 0.25)]
+                    .iter()
+                    .map(|&(u, v)| {
+                        let mut p = [0.0; 3];
+                        p[axis] = side;
+                        p[(axis + 1) % 3] = u;
+                        p[(axis + 2) % 3] = v;
+                        p
+                    })
+                    .collect();
+                for &k in [0, 1, 2, 0, 2, 3].iter() {
+                    let p = corners[k];
+                    let colour = if p[0] + p[1] > 0.0 { (state + 1) % n_states } else { state };
+                    vertices.push((turn(p), colour));
+                }
+            }
+        }
+        vec![Mark::Mesh { vertices }]
+    }
+}
+
+/// A filled circle.
+pub struct Dot;
+
+impl Glyph for Dot {
+    fn marks(&self, state: usize, _n_states: usize) -> Vec<Mark> {
+        vec![Mark::Ellipse { centre: [0.0, 0.0], size: [0.8, 0.8], rotation: 0.0, colour: state }]
+    }
+}
+
+/// A line across the cell, turned by an equal share of half a turn per state.
+pub struct Line;
+
+impl Glyph for Line {
+    fn marks(&self, state: usize, n_states: usize) -> Vec<Mark> {
+        let theta = PI * state as f32 / n_states as f32;
+        let (dy, dx) = (0.45 * theta.sin(), 0.45 * theta.cos());
+        vec![Mark::Stroke { points: vec![[-dx, -dy], [dx, dy]], closed: false, colour: state }]
+    }
+}
+
+/// Outlines from SVG files, one file per state in turn, each fitted to the
+/// cell by its `viewBox`, or by the extent of its paths if it has none.
+pub struct Svg {
+    shapes: Vec<Vec<Vec<[f32; 2]>>>,
+}
+
+/// How far a flattened SVG curve strays from the true curve, in cell units.
+const SVG_TOLERANCE: f32 = 0.005;
+
+impl Svg {
+    pub fn load<P: AsRef<Path>>(paths: &[P]) -> Result<Self, String> {
+        if paths.is_empty() {
+            return Err("an SVG glyph needs at least one file".to_string());
+        }
+        let mut shapes = Vec::new();
+        for path in paths.iter() {
+            let path = path.as_ref();
+            let text = fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+            shapes.push(svg_shape(&text).map_err(|e| format!("{}: {}", path.display(), e))?);
+        }
+        Ok(Svg { shapes })
+    }
+}
+
+impl Glyph for Svg {
+    fn marks(&self, state: usize, _n_states: usize) -> Vec<Mark> {
+        self.shapes[state % self.shapes.len()]
+            .iter()
+            .map(|contour| Mark::Stroke { points: contour.clone(), closed: true, colour: state })
+            .collect()
+    }
+}
+
+/// The contours of every `<path>` in an SVG document, fitted into the cell.
+fn svg_shape(text: &str) -> Result<Vec<Vec<[f32; 2]>>, String> {
+    // the box to fit, as min x, min y, width and height
+    let view_box: Option<Vec<f32>> = tags(text, "svg")
+        .first()
+        .and_then(|tag| attribute(tag, "viewBox"))
+        .map(|v| v.split(|c: char| c.is_whitespace() || c == ',').filter_map(|x| x.parse().ok()).collect());
+
+    // flatten finely enough for the largest side of the box to be one cell
+    let mut contours = Vec::new();
+    let tolerance = SVG_TOLERANCE * view_box.as_ref().filter(|v| v.len() == 4).map_or(1.0, |v| v[2].max(v[3]));
+    for tag in tags(text, "path") {
+        if let Some(d) = attribute(tag, "d") {
+            contours.extend(parse_svg_path(d, tolerance)?);
+        }
+    }
+    if contours.is_empty() {
+        return Err("no paths to draw".to_string());
+    }
+
+    let [x0, y0, width, height] = match view_box.as_deref() {
+        Some(&[x0, y0, width, height]) if width > 0.0 && height > 0.0 => [x0, y0, width, height],
+        _ => {
+            let points = contours.iter().flatten();
+            let (x0, x1) = points.clone().fold((f32::MAX, f32::MIN), |(a, b), p| (a.min(p[0]), b.max(p[0])));
+            let (y0, y1) = points.fold((f32::MAX, f32::MIN), |(a, b), p| (a.min(p[1]), b.max(p[1])));
+            [x0, y0, (x1 - x0).max(f32::EPSILON), (y1 - y0).max(f32::EPSILON)]
+        }
+    };
+
+    // SVG's y runs down the page
+    let scale = 1.0 / width.max(height);
+    for p in contours.iter_mut().flatten() {
+        *p = [(p[0] - x0 - 0.5 * width) * scale, (0.5 * height - (p[1] - y0)) * scale];
+    }
+    Ok(contours)
+}
+
+/// The text of every `<name ...>` tag in `text`.
+fn tags<'a>(text: &'a str, name: &str) -> Vec<&'a str> {
+    let open = format!("<{}", name);
+    text.match_indices(open.as_str())
+        .filter_map(|(k, _)| {
+            let rest = &text[k + open.len()..];
+            // skip longer names that start the same, like <pathology>
+            if !rest.starts_with(|c: char| c.is_whitespace() || c == '/' || c == '>') {
+                return None;
+            }
+            rest.find('>').map(|end| &rest[..end])
+        })
+        .collect()
+}
+
+/// The value of attribute `name` in the text of a tag.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    while let Some(k) = rest.find(name) {
+        let before = rest[..k].chars().next_back();
+        let after = rest[k + name.len()..].trim_start();
+        rest = &rest[k + name.len()..];
+        // a whole attribute name, as `d` is in ` d="..."` but not in `id="..."`
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        if let Some(value) = after.strip_prefix('=') {
+            let value = value.trim_start();
+            let quote = value.chars().next()?;
+            if quote == '"' || quote == '\'' {
+                let value = &value[1..];
+                return value.find(quote).map(|end| &value[..end]);
+            }
+        }
+    }
+    None
+}
+
+/// Names of the glyphs every sketch can pick.
+pub const BUILT_IN: [&str; 5] = ["polygon", "leaf", "cube", "dot", "line"];
+
+pub fn built_in(name: &str) -> Option<Box<dyn Glyph>> {
+    match name {
+        "polygon" => Some(Box::new(Polygon)),
+        "leaf" => Some(Box::new(Leaf)),
+        "cube" => Some(Box::new(Cube)),
+        "dot" => Some(Box::new(Dot)),
+        "line" => Some(Box::new(Line)),
+        _ => None,
+    }
+}
+
+/// A glyph named in `BUILT_IN`, or `svg:` and a comma-separated list of SVG
+/// files, one per state in turn.
+pub fn parse(spec: &str) -> Result<Box<dyn Glyph>, String> {
+    if let Some(files) = spec.strip_prefix("svg:") {
+        let paths: Vec<&str> = files.split(',').map(str::trim).filter(|p| !p.is_empty()).collect();
+        return Ok(Box::new(Svg::load(&paths)?));
+    }
+    built_in(spec).ok_or_else(|| format!("unknown glyph {:?}, expected one of {:?} or svg:file.svg", spec, BUILT_IN))
+}
@@ -0,0 +1,43 @@
+use crate::model::LatticeModel;
+use crate::random::RandomSource;
+
+impl LatticeModel {
+    /// `n_x * n_y` heat-bath updates at random sites. Each draws the site's new
+    /// state from `exp(-beta * E(state))` over all `n_states`, given its neighbours.
+    pub fn heat_bath_sweep(&mut self) {
+        self.with_own_rng(|lattice, rng| lattice.heat_bath_sweep_with(rng));
+    }
+
+    /// `heat_bath_sweep` drawing from `source`.
+    pub fn heat_bath_sweep_with<R: RandomSource + ?Sized>(&mut self, source: &mut R) {
+        let mut weights: Vec<f32> = vec![0.0; self.n_states];
+        for _ in 0..(self.n_x * self.n_y) {
+            let i: usize = source.next_index(0..self.n_x);
+            let j: usize = source.next_index(0..self.n_y);
+            let beta = self.beta.at(i, j);
+
+            // energies relative to the lowest keep the exponentials in range
+            for (state, w) in weights.iter_mut().enumerate() {
+                *w = self.site_energy(i, j, state as i8);
+            }
+            let min_energy = weights.iter().cloned().fold(f32::INFINITY, f32::min);
+            let mut total = 0.0;
+            for w in weights.iter_mut() {
+                *w = (-beta * (*w - min_energy)).exp();
+                total += *w;
+            }
+
+            // walk the cumulative distribution
+            let mut u = source.next_f32() * total;
+            let mut new_state = self.n_states - 1;
+            for (state, w) in weights.iter().enumerate() {
+                if u < *w {
+                    new_state = state;
+                    break;
+                }
+                u -= w;
+            }
+            self.a[[i, j]] = new_state as i8;
+        }
+    }
+}
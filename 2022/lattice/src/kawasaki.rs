@@ -0,0 +1,54 @@
+use crate::model::LatticeModel;
+use crate::random::RandomSource;
+
+impl LatticeModel {
+    /// Propose swapping the states of `(i, j)` and its neighbour `(ni, nj)`,
+    /// keeping the swap unless `ln(u)` exceeds `beta * (E_old - E_new)`, with
+    /// beta averaged over the pair. Returns whether the swap was accepted.
+    pub fn kawasaki(&mut self, i: usize, j: usize, ni: usize, nj: usize, u: f32) -> bool {
+        let aij = self.a[[i, j]];
+        let anij = self.a[[ni, nj]];
+        if aij == anij {
+            return false;
+        }
+
+        // the bond between the pair stays unlike, so summing the two sites is enough
+        let old_energy = self.compute_energy(i, j) + self.compute_energy(ni, nj);
+        self.a[[i, j]] = anij;
+        self.a[[ni, nj]] = aij;
+        let new_energy = self.compute_energy(i, j) + self.compute_energy(ni, nj);
+
+        // flip a coin and reject if condition is met
+        let beta = 0.5 * (self.beta.at(i, j) + self.beta.at(ni, nj));
+        if u.ln() > beta * (old_energy - new_energy) {
+            self.a[[i, j]] = aij;
+            self.a[[ni, nj]] = anij;
+            return false;
+        }
+        true
+    }
+
+    /// `n_x * n_y` exchange proposals, each between a random site and one of
+    /// its nearest neighbours in the topology picked at random. Partners
+    /// don't depend on the couplings, so spins still move along a bond with
+    /// zero coupling and never across a diagonal one. A step off an open or
+    /// fixed edge is a rejected proposal, which keeps the proposals symmetric.
+    pub fn kawasaki_sweep(&mut self) {
+        self.with_own_rng(|lattice, rng| lattice.kawasaki_sweep_with(rng));
+    }
+
+    /// `kawasaki_sweep` drawing from `source`.
+    pub fn kawasaki_sweep_with<R: RandomSource + ?Sized>(&mut self, source: &mut R) {
+        for _ in 0..(self.n_x * self.n_y) {
+            let i: usize = source.next_index(0..self.n_x);
+            let j: usize = source.next_index(0..self.n_y);
+            let offsets = self.topology.offsets(i, j);
+            let (di, dj) = offsets[source.next_index(0..offsets.len())];
+            let u = source.next_f32();
+            let (x, y) = (i as isize + di, j as isize + dj);
+            if let Some((ni, nj)) = self.boundary.resolve(x, y, self.n_x, self.n_y) {
+                self.kawasaki(i, j, ni, nj, u);
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! Shared lattice spin models for the Potts/Ising sketches.
+//!
+//! The sketches own their schedules and rendering; everything that touches
+//! the lattice itself (energy, moves, sweeps) lives here.
+
+pub mod args;
+pub mod beta_field;
+pub mod boundary;
+mod checkerboard;
+pub mod checkpoint;
+pub mod clusters;
+pub mod domains;
+pub mod field;
+pub mod glyph;
+mod heat_bath;
+mod kawasaki;
+pub mod model;
+pub mod multispin;
+pub mod observables;
+pub mod outline;
+pub mod palette;
+pub mod random;
+pub mod sampler;
+pub mod schedule;
+mod swendsen_wang;
+pub mod tempering;
+pub mod topology;
+mod wolff;
+pub mod xy;
+
+pub use beta_field::BetaField;
+pub use boundary::Boundary;
+pub use checkpoint::Checkpoint;
+pub use clusters::{ClusterRecorder, Clusters, Colouring};
+pub use domains::{DomainMap, Smoothing};
+pub use field::ExternalField;
+pub use model::{Beta, Couplings, LatticeModel};
+pub use multispin::MultispinIsing;
+pub use observables::{Observables, Recorder};
+pub use palette::Palette;
+pub use sampler::Sampler;
+pub use schedule::{Schedule, Shape};
+pub use tempering::Tempering;
+pub use topology::Topology;
+pub use xy::XyModel;
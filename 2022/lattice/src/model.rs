@@ -0,0 +1,358 @@
+use ndarray::prelude::*;
+use ndarray::{Array, Ix2};
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+
+use crate::boundary::Boundary;
+use crate::field::ExternalField;
+use crate::random::RandomSource;
+use crate::sampler::Sampler;
+use crate::topology::{Neighbors, Topology};
+
+/// Inverse temperature, either shared by the whole lattice or set per site.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Beta {
+    Global(f32),
+    Field(Array<f32, Ix2>),
+}
+
+impl Beta {
+    pub fn at(&self, i: usize, j: usize) -> f32 {
+        match self {
+            Beta::Global(beta) => *beta,
+            Beta::Field(beta) => beta[[i, j]],
+        }
+    }
+
+    /// Beta averaged over the lattice.
+    pub fn mean(&self) -> f32 {
+        match self {
+            Beta::Global(beta) => *beta,
+            Beta::Field(beta) => beta.mean().unwrap_or(0.0),
+        }
+    }
+}
+
+/// Bond strengths. A bond between equal states costs `-j`, between unequal
+/// states `+j`, so a negative coupling is antiferromagnetic.
+///
+/// `j_x` couples neighbours in the same row and `j_y` every other nearest
+/// neighbour. `j_diag` adds next-nearest bonds across the diagonals of the
+/// square lattice and is ignored by the other topologies.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Couplings {
+    pub j_x: f32,
+    pub j_y: f32,
+    pub j_diag: f32,
+}
+
+impl Couplings {
+    /// The same coupling along every nearest-neighbour bond.
+    pub fn isotropic(j: f32) -> Self {
+        Couplings { j_x: j, j_y: j, j_diag: 0.0 }
+    }
+
+    /// Whether no bond prefers unequal states, as the cluster samplers need.
+    pub fn is_ferromagnetic(&self) -> bool {
+        self.j_x >= 0.0 && self.j_y >= 0.0 && self.j_diag >= 0.0
+    }
+}
+
+impl Default for Couplings {
+    fn default() -> Self {
+        Couplings::isotropic(1.0)
+    }
+}
+
+impl FromStr for Couplings {
+    type Err = String;
+
+    /// Comma-separated `key=value` pairs over `j`, `j_x`, `j_y` and `j_diag`,
+    /// as in `j_x=1,j_y=-0.5`. `j` sets both `j_x` and `j_y`; anything left
+    /// out keeps its default.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut couplings = Couplings::default();
+        for pair in s.split(',') {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, not {:?}", pair))?;
+            let value: f32 = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid coupling {:?}", value))?;
+            match key.trim() {
+                "j" => {
+                    couplings.j_x = value;
+                    couplings.j_y = value;
+                }
+                "j_x" => couplings.j_x = value,
+                "j_y" => couplings.j_y = value,
+                "j_diag" => couplings.j_diag = value,
+                _ => return Err(format!("unknown coupling {:?}", key)),
+            }
+        }
+        Ok(couplings)
+    }
+}
+
+impl fmt::Display for Couplings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "j_x={},j_y={},j_diag={}", self.j_x, self.j_y, self.j_diag)
+    }
+}
+
+/// Bonds of site `(i, j)` on an `n_x` by `n_y` lattice, shared by every spin model.
+pub(crate) fn bonds(
+    topology: Topology,
+    boundary: Boundary,
+    couplings: &Couplings,
+    n_x: usize,
+    n_y: usize,
+    i: usize,
+    j: usize,
+) -> Neighbors {
+    let mut nn = Neighbors::new();
+    let mut bond = |di: isize, dj: isize, coupling: f32| {
+        if coupling == 0.0 {
+            return;
+        }
+        let (x, y) = (i as isize + di, j as isize + dj);
+        match boundary.resolve(x, y, n_x, n_y) {
+            Some(site) => nn.push(site, coupling),
+            None => nn.border += coupling,
+        }
+    };
+    for &(di, dj) in topology.offsets(i, j).iter() {
+        bond(di, dj, if dj == 0 { couplings.j_x } else { couplings.j_y });
+    }
+    if topology == Topology::Square {
+        for &(di, dj) in [(-1, -1), (1, -1), (-1, 1), (1, 1)].iter() {
+            bond(di, dj, couplings.j_diag);
+        }
+    }
+    nn
+}
+
+/// A q-state Potts lattice, periodic unless another boundary is chosen.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LatticeModel {
+    pub n_x: usize,
+    pub n_y: usize,
+    pub n_states: usize,
+    pub a: Array<i8, Ix2>,
+    pub topology: Topology,
+    pub boundary: Boundary,
+    pub couplings: Couplings,
+    pub field: Option<ExternalField>,
+    pub beta: Beta,
+    pub sampler: Sampler,
+    pub n_threads: usize,
+    pub seed: u64,
+    pub rng: Pcg64,
+    pub(crate) thread_rngs: Vec<Pcg64>,
+    pub(crate) wolff_mean_size: f32,
+}
+
+impl LatticeModel {
+    /// All sites start in state 0.
+    pub fn new(n_x: usize, n_y: usize, n_states: usize, beta: Beta, seed: u64) -> Self {
+        LatticeModel {
+            n_x,
+            n_y,
+            n_states,
+            a: Array::<i8, Ix2>::zeros((n_x, n_y).f()),
+            topology: Topology::Square,
+            boundary: Boundary::Periodic,
+            couplings: Couplings::default(),
+            field: None,
+            beta,
+            sampler: Sampler::Metropolis,
+            n_threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            seed,
+            rng: Pcg64::seed_from_u64(seed),
+            thread_rngs: Vec::new(),
+            wolff_mean_size: 0.0,
+        }
+    }
+
+    /// Use `boundary`, panicking if it pins the border to a state the
+    /// lattice doesn't have.
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        if let Some(state) = boundary.fixed_state() {
+            assert!(
+                0 <= state && (state as usize) < self.n_states,
+                "a fixed border needs a state from 0 to {}, not {}",
+                self.n_states - 1,
+                state
+            );
+        }
+        self.boundary = boundary;
+    }
+
+    /// Assign every site a uniformly random state.
+    pub fn randomize(&mut self) {
+        for i in 0..self.n_x {
+            for j in 0..self.n_y {
+                self.a[[i, j]] = self.rng.gen_range(0..self.n_states) as i8;
+            }
+        }
+    }
+
+    /// The sites bonded to `(i, j)` in the lattice's topology, wrapped by its
+    /// boundary, with their couplings. Bonds with zero coupling are left out,
+    /// and bonds that leave the lattice are summed into `border`.
+    pub fn neighbors(&self, i: usize, j: usize) -> Neighbors {
+        bonds(self.topology, self.boundary, &self.couplings, self.n_x, self.n_y, i, j)
+    }
+
+    /// Energy of site `(i, j)`: its bonds to its neighbours and the external field.
+    pub fn compute_energy(&self, i: usize, j: usize) -> f32 {
+        self.site_energy(i, j, self.a[[i, j]])
+    }
+
+    /// Energy site `(i, j)` would have in `state`, leaving the lattice untouched.
+    pub fn site_energy(&self, i: usize, j: usize, state: i8) -> f32 {
+        let (shared, own) = self.split_energy(i, j, state);
+        shared + own
+    }
+
+    /// Energy of the bonds shared with other sites, and of the terms that
+    /// belong to this site alone: bonds to a fixed border and the external field.
+    pub(crate) fn split_energy(&self, i: usize, j: usize, state: i8) -> (f32, f32) {
+        let nn = self.neighbors(i, j);
+        let mut shared = 0.0;
+        for (n, coupling) in nn.bonds() {
+            shared += if state == self.a[n] { -coupling } else { coupling };
+        }
+        let mut own = match self.boundary.fixed_state() {
+            Some(fixed) if fixed == state => -nn.border,
+            Some(_) => nn.border,
+            None => 0.0,
+        };
+        if let Some(field) = &self.field {
+            own += field.energy(i, j, state);
+        }
+        (shared, own)
+    }
+
+    /// Probability that site `(i, j)` in `state` is held in place by a fixed
+    /// border or by the external field, which cluster samplers treat as a bond
+    /// to one extra site that never moves.
+    pub(crate) fn anchor_probability(&self, i: usize, j: usize, state: i8, nn: &Neighbors) -> f32 {
+        let beta = self.beta.at(i, j);
+        let mut strength = 0.0;
+        if self.boundary.fixed_state() == Some(state) {
+            strength += nn.border;
+        }
+        if let Some(field) = &self.field {
+            if field.preferred[[i, j]] == state {
+                strength += field.h[[i, j]];
+            }
+        }
+        1.0 - (-2.0 * beta * strength).exp()
+    }
+
+    /// Propose `new_state` at `(i, j)` and keep it unless `ln(u)` exceeds
+    /// `beta * (E_old - E_new)`. Returns whether the move was accepted.
+    pub fn metropolis(&mut self, i: usize, j: usize, new_state: i8, u: f32) -> bool {
+        // compute energy
+        let old_energy = self.compute_energy(i, j);
+
+        // make a move
+        let old_aij = self.a[[i, j]];
+        self.a[[i, j]] = new_state;
+
+        // compute energy
+        let new_energy = self.compute_energy(i, j);
+
+        // flip a coin and reject if condition is met
+        if u.ln() > self.beta.at(i, j) * (old_energy - new_energy) {
+            self.a[[i, j]] = old_aij;
+            return false;
+        }
+        true
+    }
+
+    /// One Monte Carlo sweep with the selected sampler.
+    pub fn sweep(&mut self) {
+        match self.sampler {
+            // its bands draw from streams of their own, one per thread
+            Sampler::Checkerboard => self.checkerboard_sweep(),
+            _ => self.with_own_rng(|lattice, rng| lattice.sweep_with(rng)),
+        }
+    }
+
+    /// `sweep` with every draw taken from `source` instead of the lattice's
+    /// own stream, to see what a generator does to the physics. Checkerboard
+    /// sweeps take their bands one after another from the one source.
+    pub fn sweep_with<R: RandomSource + ?Sized>(&mut self, source: &mut R) {
+        match self.sampler {
+            Sampler::Metropolis => self.metropolis_sweep_with(source),
+            Sampler::Wolff => self.wolff_sweep_with(source),
+            Sampler::SwendsenWang => self.swendsen_wang_sweep_with(source),
+            Sampler::Checkerboard => self.checkerboard_sweep_with(source),
+            Sampler::HeatBath => self.heat_bath_sweep_with(source),
+            Sampler::Kawasaki => self.kawasaki_sweep_with(source),
+        }
+    }
+
+    /// Run `f` with the lattice's own stream as its random source.
+    pub(crate) fn with_own_rng<T>(&mut self, f: impl FnOnce(&mut Self, &mut Pcg64) -> T) -> T {
+        let mut rng = std::mem::replace(&mut self.rng, Pcg64::new(0, 0));
+        let result = f(self, &mut rng);
+        self.rng = rng;
+        result
+    }
+
+    /// `n_x * n_y` single-site proposals at random sites.
+    pub fn metropolis_sweep(&mut self) {
+        self.with_own_rng(|lattice, rng| lattice.metropolis_sweep_with(rng));
+    }
+
+    /// `metropolis_sweep` drawing from `source`.
+    pub fn metropolis_sweep_with<R: RandomSource + ?Sized>(&mut self, source: &mut R) {
+        for _ in 0..(self.n_x * self.n_y) {
+            let i: usize = source.next_index(0..self.n_x);
+            let j: usize = source.next_index(0..self.n_y);
+            let new_state = source.next_index(0..self.n_states) as i8;
+            let u = source.next_f32();
+            self.metropolis(i, j, new_state, u);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn couplings_parse_keys_over_the_defaults() {
+        assert_eq!("j_x=1,j_y=-0.5".parse(), Ok(Couplings { j_x: 1.0, j_y: -0.5, j_diag: 0.0 }));
+        assert_eq!(" j = 2 , j_diag = 0.25 ".parse(), Ok(Couplings { j_x: 2.0, j_y: 2.0, j_diag: 0.25 }));
+        assert_eq!("j_diag=-1".parse(), Ok(Couplings { j_x: 1.0, j_y: 1.0, j_diag: -1.0 }));
+    }
+
+    #[test]
+    fn couplings_later_keys_win() {
+        assert_eq!("j=3,j_y=0".parse(), Ok(Couplings { j_x: 3.0, j_y: 0.0, j_diag: 0.0 }));
+        assert_eq!("j_y=0,j=3".parse(), Ok(Couplings { j_x: 3.0, j_y: 3.0, j_diag: 0.0 }));
+    }
+
+    #[test]
+    fn couplings_reject_bad_pairs() {
+        assert!("j_z=1".parse::<Couplings>().is_err());
+        assert!("j_x".parse::<Couplings>().is_err());
+        assert!("j_x=strong".parse::<Couplings>().is_err());
+        assert!("".parse::<Couplings>().is_err());
+    }
+
+    #[test]
+    fn couplings_round_trip_through_display() {
+        let couplings = Couplings { j_x: 0.5, j_y: -2.0, j_diag: 0.125 };
+        assert_eq!(couplings.to_string().parse(), Ok(couplings));
+    }
+}
@@ -0,0 +1,337 @@
+//! Ising spins packed 64 to a word, for lattices too big to step one site
+//! at a time.
+//!
+//! Each row of the lattice is a run of `u64` words with site `i` at bit
+//! `i % 64` of word `i / 64`, a set bit being state 1. A sweep updates one
+//! checkerboard colour at a time, working out the Metropolis outcome of every
+//! site of that colour in a word at once: the neighbours it disagrees with
+//! are counted with bitwise adders, and the acceptance coin flips come from
+//! random words whose bits are each set with the acceptance probability.
+//!
+//! Only the square lattice with periodic boundaries, one coupling and one
+//! beta is covered; everything else is left to `LatticeModel`.
+
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use std::thread;
+
+use crate::observables::Observables;
+use crate::random::thread_streams;
+
+/// Binary digits of an acceptance probability the coin flips resolve, as
+/// many as an `f32` draw has.
+const PROBABILITY_BITS: u32 = 24;
+
+/// A periodic square Ising lattice stored a bit per site.
+pub struct MultispinIsing {
+    pub n_x: usize,
+    pub n_y: usize,
+    pub beta: f32,
+    /// Bond strength, as in `Couplings`: a bond between equal spins costs
+    /// `-j`, so a negative coupling is antiferromagnetic.
+    pub j: f32,
+    pub n_threads: usize,
+    pub seed: u64,
+    pub rng: Pcg64,
+    n_words: usize,
+    words: Vec<u64>,
+    thread_rngs: Vec<Pcg64>,
+}
+
+impl MultispinIsing {
+    /// All sites start in state 0. Both sides must be even for the
+    /// checkerboard to hold across the periodic seam.
+    pub fn new(n_x: usize, n_y: usize, beta: f32, seed: u64) -> Self {
+        assert!(n_x > 0 && n_y > 0, "an empty lattice has nothing to sweep");
+        assert!(n_x.is_multiple_of(2) && n_y.is_multiple_of(2), "multispin lattices need even sides, not {} by {}", n_x, n_y);
+        let n_words = n_x.div_ceil(64);
+        MultispinIsing {
+            n_x,
+            n_y,
+            beta,
+            j: 1.0,
+            n_threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            seed,
+            rng: Pcg64::seed_from_u64(seed),
+            n_words,
+            words: vec![0; n_words * n_y],
+            thread_rngs: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> i8 {
+        ((self.words[j * self.n_words + i / 64] >> (i % 64)) & 1) as i8
+    }
+
+    pub fn set(&mut self, i: usize, j: usize, state: i8) {
+        let word = &mut self.words[j * self.n_words + i / 64];
+        if state == 0 {
+            *word &= !(1 << (i % 64));
+        } else {
+            *word |= 1 << (i % 64);
+        }
+    }
+
+    /// Put every site in `state`.
+    pub fn fill(&mut self, state: i8) {
+        for w in 0..self.words.len() {
+            self.words[w] = if state == 0 { 0 } else { self.valid(w % self.n_words) };
+        }
+    }
+
+    /// Assign every site a uniformly random state.
+    pub fn randomize(&mut self) {
+        for w in 0..self.words.len() {
+            self.words[w] = self.rng.next_u64() & self.valid(w % self.n_words);
+        }
+    }
+
+    /// Bits of word `w` in a row that hold sites, the rest being padding past `n_x`.
+    fn valid(&self, w: usize) -> u64 {
+        let n_bits = self.n_x - 64 * w;
+        if n_bits >= 64 {
+            !0
+        } else {
+            (1 << n_bits) - 1
+        }
+    }
+
+    /// Word `w` of `row` with every site replaced by its neighbour at `i + 1`.
+    fn right(&self, row: &[u64], w: usize) -> u64 {
+        let next = (w + 1) % self.n_words;
+        let top = if w == self.n_words - 1 { self.n_x - 64 * w - 1 } else { 63 };
+        (row[w] >> 1) | ((row[next] & 1) << top)
+    }
+
+    /// Word `w` of `row` with every site replaced by its neighbour at `i - 1`.
+    fn left(&self, row: &[u64], w: usize) -> u64 {
+        let previous = (w + self.n_words - 1) % self.n_words;
+        let top = if previous == self.n_words - 1 { self.n_x - 64 * previous - 1 } else { 63 };
+        (row[w] << 1) | ((row[previous] >> top) & 1)
+    }
+
+    fn row(&self, j: usize) -> &[u64] {
+        &self.words[j * self.n_words..(j + 1) * self.n_words]
+    }
+
+    /// Metropolis over every site, one checkerboard colour at a time, with
+    /// rows split into bands proposed in parallel. Thread `k` draws from PCG
+    /// stream `k` of `seed`, so a run is reproducible for a fixed `n_threads`.
+    pub fn sweep(&mut self) {
+        let n_threads = self.n_threads.max(1).min(self.n_y);
+        let mut rngs = thread_streams(&mut self.thread_rngs, self.seed, n_threads);
+
+        // flipping a spin that disagrees with k of its four neighbours costs
+        // 4 |j| (2 - k), so one disagreement is accepted with probability p
+        // and none with p^2
+        let p = (-4.0 * self.beta * self.j.abs()).exp();
+        let threshold = (p.min(1.0) as f64 * (1u64 << PROBABILITY_BITS) as f64) as u64;
+
+        for colour in 0..2 {
+            let this = &*self;
+            let bands: Vec<Vec<u64>> = thread::scope(|s| {
+                let handles: Vec<_> = rngs
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(k, rng)| {
+                        let j0 = k * this.n_y / n_threads;
+                        let j1 = (k + 1) * this.n_y / n_threads;
+                        s.spawn(move || this.propose_band(colour, j0, j1, threshold, rng))
+                    })
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            });
+            for (k, band) in bands.into_iter().enumerate() {
+                let j0 = k * self.n_y / n_threads;
+                self.words[j0 * self.n_words..j0 * self.n_words + band.len()].copy_from_slice(&band);
+            }
+        }
+
+        self.thread_rngs = rngs;
+    }
+
+    /// Rows `j0..j1` after Metropolis on their sites of `colour`.
+    fn propose_band(&self, colour: usize, j0: usize, j1: usize, threshold: u64, rng: &mut Pcg64) -> Vec<u64> {
+        // an antiferromagnet pays for agreeing instead
+        let agree: u64 = if self.j < 0.0 { !0 } else { 0 };
+        let mut band = Vec::with_capacity((j1 - j0) * self.n_words);
+        for j in j0..j1 {
+            let row = self.row(j);
+            let up = self.row((j + self.n_y - 1) % self.n_y);
+            let down = self.row((j + 1) % self.n_y);
+            let checkerboard = if (j + colour).is_multiple_of(2) { 0x5555_5555_5555_5555 } else { 0xaaaa_aaaa_aaaa_aaaa };
+            for w in 0..self.n_words {
+                let s = row[w];
+                let x1 = s ^ self.left(row, w) ^ agree;
+                let x2 = s ^ self.right(row, w) ^ agree;
+                let x3 = s ^ up[w] ^ agree;
+                let x4 = s ^ down[w] ^ agree;
+
+                // count the disagreements in pairs: none, one, or two or more
+                let (a1, c1) = (x1 ^ x2, x1 & x2);
+                let (a2, c2) = (x3 ^ x4, x3 & x4);
+                let many = c1 | c2 | (a1 & a2);
+                let one = (a1 ^ a2) & !(c1 | c2);
+                let none = !(x1 | x2 | x3 | x4);
+
+                let sites = checkerboard & self.valid(w);
+                let mut flip = many;
+                if (one | none) & sites != 0 {
+                    let coin = bernoulli(threshold, rng);
+                    flip |= one & coin;
+                    if none & coin & sites != 0 {
+                        flip |= none & coin & bernoulli(threshold, rng);
+                    }
+                }
+                band.push(s ^ (flip & sites));
+            }
+        }
+        band
+    }
+
+    /// Write the lattice into `rgba` as an image `w_x` pixels per site across
+    /// and `w_y` down, row after row from `j = 0`, ready to upload to a texture.
+    pub fn unpack_rgba(&self, down_rgba: [u8; 4], up_rgba: [u8; 4], w_x: usize, w_y: usize, rgba: &mut [u8]) {
+        let row_bytes = 4 * self.n_x * w_x;
+        assert_eq!(rgba.len(), row_bytes * self.n_y * w_y, "image doesn't match the lattice");
+        for (j, rows) in rgba.chunks_exact_mut(row_bytes * w_y).enumerate() {
+            let (first, rest) = rows.split_at_mut(row_bytes);
+            for (i, pixels) in first.chunks_exact_mut(4 * w_x).enumerate() {
+                let bit = (self.words[j * self.n_words + i / 64] >> (i % 64)) & 1;
+                let rgba = if bit == 0 { &down_rgba } else { &up_rgba };
+                for pixel in pixels.chunks_exact_mut(4) {
+                    pixel.copy_from_slice(rgba);
+                }
+            }
+            for row in rest.chunks_exact_mut(row_bytes) {
+                row.copy_from_slice(first);
+            }
+        }
+    }
+}
+
+/// A random word with each bit set with probability `threshold / 2^PROBABILITY_BITS`.
+///
+/// Each bit compares its own uniform number, one random word per binary
+/// digit, against the probability. Working up from the last digit, a `1` in
+/// the probability keeps a bit that was already below or sets it on a random
+/// digit of `0`, and a `0` keeps it only on a random `0` as well.
+fn bernoulli(threshold: u64, rng: &mut Pcg64) -> u64 {
+    if threshold == 0 {
+        return 0;
+    }
+    let mut word = 0;
+    for k in threshold.trailing_zeros()..PROBABILITY_BITS {
+        word = if (threshold >> k) & 1 == 1 { word | rng.next_u64() } else { word & rng.next_u64() };
+    }
+    if threshold >> PROBABILITY_BITS != 0 {
+        !0
+    } else {
+        word
+    }
+}
+
+impl Observables for MultispinIsing {
+    fn n_sites(&self) -> usize {
+        self.n_x * self.n_y
+    }
+
+    fn mean_beta(&self) -> f32 {
+        self.beta
+    }
+
+    /// `|m|`, the same as the Potts order parameter for two states.
+    fn magnetization(&self) -> f32 {
+        let n_up: u32 = self.words.iter().map(|w| w.count_ones()).sum();
+        (2.0 * n_up as f32 / self.n_sites() as f32 - 1.0).abs()
+    }
+
+    /// Bonds to the right and below each site, so each is counted once.
+    fn energy_per_site(&self) -> f32 {
+        let mut n_unequal: u64 = 0;
+        for j in 0..self.n_y {
+            let row = self.row(j);
+            let down = self.row((j + 1) % self.n_y);
+            for w in 0..self.n_words {
+                let unequal = [row[w] ^ self.right(row, w), row[w] ^ down[w]];
+                n_unequal += unequal.iter().map(|u| (u & self.valid(w)).count_ones() as u64).sum::<u64>();
+            }
+        }
+        let n_bonds = 2 * self.n_sites() as u64;
+        self.j * (2.0 * n_unequal as f32 - n_bonds as f32) / self.n_sites() as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Beta, Couplings, LatticeModel};
+
+    /// The same spins as a general lattice, with the same coupling.
+    fn unpacked(lattice: &MultispinIsing) -> LatticeModel {
+        let mut model = LatticeModel::new(lattice.n_x, lattice.n_y, 2, Beta::Global(lattice.beta), lattice.seed);
+        model.couplings = Couplings::isotropic(lattice.j);
+        for i in 0..lattice.n_x {
+            for j in 0..lattice.n_y {
+                model.a[[i, j]] = lattice.get(i, j);
+            }
+        }
+        model
+    }
+
+    fn total_energy<L: Observables>(lattice: &L) -> f32 {
+        lattice.energy_per_site() * lattice.n_sites() as f32
+    }
+
+    #[test]
+    fn energy_matches_the_lattice_model_across_words() {
+        // 70 sites to a row, so every row spills into a second word
+        for &coupling in [1.0, -1.0, 0.5].iter() {
+            let mut lattice = MultispinIsing::new(70, 6, 0.4, 7);
+            lattice.j = coupling;
+            lattice.randomize();
+            let model = unpacked(&lattice);
+            assert!((total_energy(&lattice) - total_energy(&model)).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn flip_costs_match_the_lattice_model() {
+        for &coupling in [1.0, -1.0].iter() {
+            let mut lattice = MultispinIsing::new(70, 6, 0.4, 11);
+            lattice.j = coupling;
+            lattice.randomize();
+            let model = unpacked(&lattice);
+            // the seams of the words and of the periodic edges included
+            for &(i, j) in [(0, 0), (63, 2), (64, 3), (69, 5), (30, 4)].iter() {
+                let before = total_energy(&lattice);
+                lattice.set(i, j, 1 - lattice.get(i, j));
+                let delta = total_energy(&lattice) - before;
+                lattice.set(i, j, 1 - lattice.get(i, j));
+
+                let old_state = model.a[[i, j]];
+                let expected = model.site_energy(i, j, 1 - old_state) - model.compute_energy(i, j);
+                assert!((delta - expected).abs() < 1e-3, "site ({}, {}): {} against {}", i, j, delta, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn cold_sweeps_never_raise_the_energy() {
+        // at this beta every uphill flip is rejected, so by the lattice
+        // model's reckoning the energy can only fall or hold
+        for &coupling in [1.0, -1.0].iter() {
+            let mut lattice = MultispinIsing::new(70, 8, 50.0, 3);
+            lattice.j = coupling;
+            lattice.n_threads = 2;
+            lattice.randomize();
+            let mut energy = total_energy(&unpacked(&lattice));
+            for _ in 0..5 {
+                lattice.sweep();
+                let swept = total_energy(&unpacked(&lattice));
+                assert!(swept <= energy + 1e-3, "energy rose from {} to {}", energy, swept);
+                energy = swept;
+            }
+        }
+    }
+}
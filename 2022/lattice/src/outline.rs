@@ -0,0 +1,286 @@
+//! Outlines made of straight and curved pieces, as glyphs and SVG paths are,
+//! turned into polylines and point samples for the beta fields.
+//!
+//! Contours are lists of points, closed back to their first point. Inside
+//! and outside follow the nonzero winding rule, as font rasterisers do, so
+//! the holes of letters like `O` and `B` stay empty.
+
+use ndarray::prelude::*;
+use ndarray::{Array, Ix2};
+
+use crate::beta_field::Grid;
+
+/// One piece of an outline.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Curve {
+    Line([f32; 2], [f32; 2]),
+    /// Quadratic Bézier from the first point to the last, pulled towards the middle one.
+    Quadratic([f32; 2], [f32; 2], [f32; 2]),
+    /// Cubic Bézier from the first point to the last, with two control points.
+    Cubic([f32; 2], [f32; 2], [f32; 2], [f32; 2]),
+}
+
+impl Curve {
+    pub fn point(&self, t: f32) -> [f32; 2] {
+        let s = 1.0 - t;
+        match *self {
+            Curve::Line(a, b) => mix(&[(a, s), (b, t)]),
+            Curve::Quadratic(a, c, b) => mix(&[(a, s * s), (c, 2.0 * s * t), (b, t * t)]),
+            Curve::Cubic(a, c1, c2, b) => mix(&[(a, s * s * s), (c1, 3.0 * s * s * t), (c2, 3.0 * s * t * t), (b, t * t * t)]),
+        }
+    }
+
+    /// Points along the curve, both ends included, with no chord straying
+    /// more than `tolerance` from the curve.
+    pub fn flatten(&self, tolerance: f32) -> Vec<[f32; 2]> {
+        // a chord over a parameter step h strays at most h^2 / 8 times the
+        // largest second derivative
+        let bend = match *self {
+            Curve::Line(..) => 0.0,
+            Curve::Quadratic(a, c, b) => 2.0 * norm(second_difference(a, c, b)),
+            Curve::Cubic(a, c1, c2, b) => 6.0 * norm(second_difference(a, c1, c2)).max(norm(second_difference(c1, c2, b))),
+        };
+        let n = ((bend / (8.0 * tolerance.max(f32::EPSILON))).sqrt().ceil() as usize).max(1);
+        (0..=n).map(|k| self.point(k as f32 / n as f32)).collect()
+    }
+}
+
+fn mix(terms: &[([f32; 2], f32)]) -> [f32; 2] {
+    terms.iter().fold([0.0, 0.0], |acc, &(p, w)| [acc[0] + w * p[0], acc[1] + w * p[1]])
+}
+
+fn second_difference(a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> [f32; 2] {
+    [a[0] - 2.0 * b[0] + c[0], a[1] - 2.0 * b[1] + c[1]]
+}
+
+fn norm(v: [f32; 2]) -> f32 {
+    (v[0] * v[0] + v[1] * v[1]).sqrt()
+}
+
+/// Points every `spacing` along a contour, measured along its length and
+/// starting at its first point, including the closing edge. `spacing` must
+/// be positive.
+pub fn resample(contour: &[[f32; 2]], spacing: f32) -> Vec<[f32; 2]> {
+    assert!(spacing > 0.0, "resampling needs a positive spacing, not {}", spacing);
+    let mut samples = Vec::new();
+    if contour.is_empty() {
+        return samples;
+    }
+    samples.push(contour[0]);
+    let mut carried = 0.0;
+    for (k, &a) in contour.iter().enumerate() {
+        let b = contour[(k + 1) % contour.len()];
+        let length = norm([b[0] - a[0], b[1] - a[1]]);
+        let mut along = spacing - carried;
+        while along < length {
+            let t = along / length;
+            samples.push([a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])]);
+            along += spacing;
+        }
+        carried = length - (along - spacing);
+    }
+    samples
+}
+
+/// How many times the contours wind round `p`, counter-clockwise positive.
+pub fn winding_number(contours: &[Vec<[f32; 2]>], p: [f32; 2]) -> i32 {
+    let mut winding = 0;
+    for contour in contours.iter() {
+        for (k, &a) in contour.iter().enumerate() {
+            let b = contour[(k + 1) % contour.len()];
+            let side = (b[0] - a[0]) * (p[1] - a[1]) - (p[0] - a[0]) * (b[1] - a[1]);
+            if a[1] <= p[1] && b[1] > p[1] && side > 0.0 {
+                winding += 1;
+            } else if a[1] > p[1] && b[1] <= p[1] && side < 0.0 {
+                winding -= 1;
+            }
+        }
+    }
+    winding
+}
+
+/// 1 at the sites of `grid` inside the contours and 0 elsewhere, found a
+/// row at a time from where the edges cross it.
+pub fn fill_mask(contours: &[Vec<[f32; 2]>], grid: &Grid) -> Array<f32, Ix2> {
+    let mut mask = Array::<f32, Ix2>::zeros((grid.n_x, grid.n_y).f());
+    let mut crossings: Vec<(f32, i32)> = Vec::new();
+    for j in 0..grid.n_y {
+        let y = grid.y0 + j as f32 * grid.w_y;
+        crossings.clear();
+        for contour in contours.iter() {
+            for (k, &a) in contour.iter().enumerate() {
+                let b = contour[(k + 1) % contour.len()];
+                let direction = if a[1] <= y && b[1] > y {
+                    1
+                } else if a[1] > y && b[1] <= y {
+                    -1
+                } else {
+                    continue;
+                };
+                let x = a[0] + (y - a[1]) / (b[1] - a[1]) * (b[0] - a[0]);
+                crossings.push((x, direction));
+            }
+        }
+        if crossings.is_empty() {
+            continue;
+        }
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // sweep along the row, tallying the edges passed so far
+        let (mut k, mut winding) = (0, 0);
+        for i in 0..grid.n_x {
+            let x = grid.x0 + i as f32 * grid.w_x;
+            while k < crossings.len() && crossings[k].0 <= x {
+                winding += crossings[k].1;
+                k += 1;
+            }
+            if winding != 0 {
+                mask[[i, j]] = 1.0;
+            }
+        }
+    }
+    mask
+}
+
+/// Contours of SVG path data, the `d` attribute of a `<path>`, with curves
+/// flattened to within `tolerance`. Every subpath is taken as closed, and
+/// arcs aren't supported.
+pub fn parse_svg_path(d: &str, tolerance: f32) -> Result<Vec<Vec<[f32; 2]>>, String> {
+    let tokens = svg_tokens(d)?;
+    let mut contours: Vec<Vec<[f32; 2]>> = Vec::new();
+    let (mut current, mut start) = ([0.0, 0.0], [0.0, 0.0]);
+    // the control point a smooth curve reflects, from the last C/S or Q/T
+    let (mut last_cubic, mut last_quadratic): (Option<[f32; 2]>, Option<[f32; 2]>) = (None, None);
+    let mut command = None;
+    // after a closepath, the next segment starts a new contour from the closed one's start
+    let mut closed = true;
+    let mut k = 0;
+    while k < tokens.len() {
+        let c = match tokens[k] {
+            SvgToken::Command(c) => {
+                k += 1;
+                c
+            }
+            // numbers after a command's first set repeat it, a moveto as a lineto
+            SvgToken::Number(_) => match command {
+                Some('M') => 'L',
+                Some('m') => 'l',
+                Some(c) => c,
+                None => return Err("path data must start with a command".to_string()),
+            },
+        };
+        let n_args = match c.to_ascii_uppercase() {
+            'Z' => 0,
+            'H' | 'V' => 1,
+            'M' | 'L' | 'T' => 2,
+            'S' | 'Q' => 4,
+            'C' => 6,
+            'A' => return Err("arcs aren't supported in SVG paths".to_string()),
+            _ => return Err(format!("unknown SVG path command {:?}", c)),
+        };
+        let mut args = [0.0f32; 6];
+        for arg in args.iter_mut().take(n_args) {
+            match tokens.get(k) {
+                Some(&SvgToken::Number(x)) => *arg = x,
+                _ => return Err(format!("SVG path command {:?} needs {} numbers", c, n_args)),
+            }
+            k += 1;
+        }
+
+        // relative coordinates are offsets from the current point
+        let relative = c.is_ascii_lowercase();
+        let at = |x: f32, y: f32| if relative { [current[0] + x, current[1] + y] } else { [x, y] };
+        let curve = match c.to_ascii_uppercase() {
+            'M' => {
+                current = at(args[0], args[1]);
+                start = current;
+                contours.push(vec![current]);
+                closed = false;
+                None
+            }
+            'Z' => {
+                current = start;
+                closed = true;
+                None
+            }
+            'L' => Some(Curve::Line(current, at(args[0], args[1]))),
+            'H' => Some(Curve::Line(current, [if relative { current[0] + args[0] } else { args[0] }, current[1]])),
+            'V' => Some(Curve::Line(current, [current[0], if relative { current[1] + args[0] } else { args[0] }])),
+            'C' => Some(Curve::Cubic(current, at(args[0], args[1]), at(args[2], args[3]), at(args[4], args[5]))),
+            'S' => {
+                let c1 = last_cubic.map_or(current, |c| [2.0 * current[0] - c[0], 2.0 * current[1] - c[1]]);
+                Some(Curve::Cubic(current, c1, at(args[0], args[1]), at(args[2], args[3])))
+            }
+            'Q' => Some(Curve::Quadratic(current, at(args[0], args[1]), at(args[2], args[3]))),
+            _ => {
+                let c = last_quadratic.map_or(current, |c| [2.0 * current[0] - c[0], 2.0 * current[1] - c[1]]);
+                Some(Curve::Quadratic(current, c, at(args[0], args[1])))
+            }
+        };
+        last_cubic = None;
+        last_quadratic = None;
+        if let Some(curve) = curve {
+            match curve {
+                Curve::Cubic(_, _, c2, _) => last_cubic = Some(c2),
+                Curve::Quadratic(_, c, _) => last_quadratic = Some(c),
+                Curve::Line(..) => {}
+            }
+            if closed {
+                contours.push(vec![current]);
+                closed = false;
+            }
+            let points = curve.flatten(tolerance);
+            current = points[points.len() - 1];
+            contours.last_mut().unwrap().extend_from_slice(&points[1..]);
+        }
+        command = Some(c);
+    }
+    // a moveto with nothing drawn from it leaves a lone point
+    contours.retain(|contour| contour.len() > 1);
+    Ok(contours)
+}
+
+enum SvgToken {
+    Command(char),
+    Number(f32),
+}
+
+/// Commands and numbers of SVG path data, which may run together as in
+/// `M1-2.5.5`, where the numbers are 1, -2.5 and 0.5.
+fn svg_tokens(d: &str) -> Result<Vec<SvgToken>, String> {
+    let bytes = d.as_bytes();
+    let mut tokens = Vec::new();
+    let mut k = 0;
+    while k < bytes.len() {
+        let b = bytes[k];
+        if b.is_ascii_whitespace() || b == b',' {
+            k += 1;
+        } else if b.is_ascii_alphabetic() && b != b'e' && b != b'E' {
+            tokens.push(SvgToken::Command(b as char));
+            k += 1;
+        } else {
+            let begin = k;
+            if b == b'+' || b == b'-' {
+                k += 1;
+            }
+            let mut seen_point = false;
+            while k < bytes.len() && (bytes[k].is_ascii_digit() || (bytes[k] == b'.' && !seen_point)) {
+                seen_point |= bytes[k] == b'.';
+                k += 1;
+            }
+            if k < bytes.len() && (bytes[k] == b'e' || bytes[k] == b'E') {
+                k += 1;
+                if k < bytes.len() && (bytes[k] == b'+' || bytes[k] == b'-') {
+                    k += 1;
+                }
+                while k < bytes.len() && bytes[k].is_ascii_digit() {
+                    k += 1;
+                }
+            }
+            let number = &d[begin..k];
+            let x = number.parse().map_err(|_| format!("invalid number {:?} in SVG path", number))?;
+            tokens.push(SvgToken::Number(x));
+        }
+    }
+    Ok(tokens)
+}
@@ -0,0 +1,220 @@
+//! Colour palettes for the lattice sketches, one RGBA entry per state.
+//!
+//! A sketch takes its palette at launch, by name from the library below or
+//! from a file, and gets one state per colour. Files come in three kinds,
+//! told apart by extension:
+//!
+//! - hex lists (any other extension): `RRGGBB` or `RRGGBBAA` colours, with or
+//!   without a leading `#`, separated by whitespace or commas;
+//! - GIMP palettes (`.gpl`): a `GIMP Palette` header, then `r g b [name]`
+//!   lines, with `#` comments;
+//! - JSON (`.json`): a list of colours, or an object with the list under
+//!   `colours` or `colors`, each colour a hex string or `[r, g, b]` or
+//!   `[r, g, b, a]`.
+
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The palettes every sketch can name, as hex lists.
+pub const LIBRARY: [(&str, &str); 9] = [
+    ("mono", "#000000 #FFFFFF"),
+    ("pink", "#FF3032 #FF6065 #FF8D97 #FFC0CB #FFFFFF"),
+    ("pink-light", "#FF8D97 #FFC0CB #FFFFFF"),
+    ("pink-dark", "#FF464B #FF8D97 #FFC0CB"),
+    ("forest", "#667C26 #387C44 #254117"),
+    ("violet", "#2E294E #541388 #F1E9DA #FFD400 #D90368"),
+    ("ocean", "#03045E #0077B6 #00B4D8 #90E0EF #CAF0F8"),
+    ("sunset", "#355070 #6D597A #B56576 #E56B6F #EAAC8B"),
+    ("viridis", "#440154 #3B528B #21918C #5EC962 #FDE725"),
+];
+
+/// Most states a lattice can hold, as they are stored in an `i8`.
+const MAX_COLOURS: usize = i8::MAX as usize;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colours: Vec<[u8; 4]>,
+}
+
+impl Palette {
+    /// At least two colours, one per state, and no more states than a lattice holds.
+    pub fn new(colours: Vec<[u8; 4]>) -> Result<Self, String> {
+        if colours.len() < 2 || colours.len() > MAX_COLOURS {
+            return Err(format!("a palette needs 2 to {} colours, not {}", MAX_COLOURS, colours.len()));
+        }
+        Ok(Palette { colours })
+    }
+
+    /// A palette from `LIBRARY`.
+    pub fn named(name: &str) -> Option<Self> {
+        LIBRARY
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, hex)| Palette::from_hex(hex).unwrap())
+    }
+
+    /// Read a palette file, in the format its extension calls for.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let text = fs::read_to_string(&path)?;
+        let palette = match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("gpl") => Palette::from_gpl(&text),
+            Some("json") => Palette::from_json(&text),
+            _ => Palette::from_hex(&text),
+        };
+        palette.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn from_hex(s: &str) -> Result<Self, String> {
+        let colours = s
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|token| !token.is_empty())
+            .map(parse_hex)
+            .collect::<Result<_, _>>()?;
+        Palette::new(colours)
+    }
+
+    pub fn from_gpl(s: &str) -> Result<Self, String> {
+        let mut lines = s.lines();
+        if lines.next().map(str::trim) != Some("GIMP Palette") {
+            return Err("a GIMP palette starts with `GIMP Palette`".to_string());
+        }
+        let mut colours = Vec::new();
+        for line in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("Name:") || line.starts_with("Columns:") {
+                continue;
+            }
+            // the channels, then an optional name for the colour
+            let channels: Vec<&str> = line.split_whitespace().take(3).collect();
+            if channels.len() < 3 {
+                return Err(format!("expected `r g b [name]`, got {:?}", line));
+            }
+            let mut rgba = [0, 0, 0, u8::MAX];
+            for (c, channel) in rgba.iter_mut().zip(channels) {
+                *c = channel.parse().map_err(|_| format!("invalid channel {:?}", channel))?;
+            }
+            colours.push(rgba);
+        }
+        Palette::new(colours)
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, String> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Colour {
+            Hex(String),
+            Channels(Vec<u8>),
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Json {
+            List(Vec<Colour>),
+            Object {
+                #[serde(alias = "colors")]
+                colours: Vec<Colour>,
+            },
+        }
+
+        let colours = match serde_json::from_str(s).map_err(|e| e.to_string())? {
+            Json::List(colours) | Json::Object { colours } => colours,
+        };
+        let colours = colours
+            .into_iter()
+            .map(|colour| match colour {
+                Colour::Hex(hex) => parse_hex(&hex),
+                Colour::Channels(c) => match c[..] {
+                    [r, g, b] => Ok([r, g, b, u8::MAX]),
+                    [r, g, b, a] => Ok([r, g, b, a]),
+                    _ => Err(format!("expected 3 or 4 channels, got {:?}", c)),
+                },
+            })
+            .collect::<Result<_, _>>()?;
+        Palette::new(colours)
+    }
+
+    /// Number of colours, and so of states.
+    pub fn len(&self) -> usize {
+        self.colours.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colours.is_empty()
+    }
+}
+
+/// `RRGGBB` or `RRGGBBAA`, with or without a leading `#`.
+fn parse_hex(token: &str) -> Result<[u8; 4], String> {
+    let digits = token.strip_prefix('#').unwrap_or(token);
+    if !(digits.len() == 6 || digits.len() == 8) || !digits.is_ascii() {
+        return Err(format!("invalid colour {:?}", token));
+    }
+    let mut rgba = [0, 0, 0, u8::MAX];
+    for (k, c) in rgba.iter_mut().enumerate().take(digits.len() / 2) {
+        *c = u8::from_str_radix(&digits[2 * k..2 * k + 2], 16).map_err(|_| format!("invalid colour {:?}", token))?;
+    }
+    Ok(rgba)
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /// A name from `LIBRARY`, a palette file, or a hex list given inline.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(palette) = Palette::named(s) {
+            return Ok(palette);
+        }
+        if Path::new(s).is_file() {
+            return Palette::load(s).map_err(|e| format!("can't read palette {:?}: {}", s, e));
+        }
+        Palette::from_hex(s).map_err(|e| format!("unknown palette {:?}: {}", s, e))
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hex: Vec<String> = self
+            .colours
+            .iter()
+            .map(|c| match c[3] {
+                u8::MAX => format!("#{:02X}{:02X}{:02X}", c[0], c[1], c[2]),
+                _ => format!("#{:02X}{:02X}{:02X}{:02X}", c[0], c[1], c[2], c[3]),
+            })
+            .collect();
+        write!(f, "{}", hex.join(","))
+    }
+}
+
+/// Fully saturated colour at `angle` radians round the hue wheel, red at zero.
+pub fn hue(angle: f32) -> [u8; 4] {
+    let h = angle.rem_euclid(2.0 * std::f32::consts::PI) / (std::f32::consts::PI / 3.0);
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    let (r, g, b) = match h as usize {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    let channel = |c: f32| (c * u8::MAX as f32).round() as u8;
+    [channel(r), channel(g), channel(b), u8::MAX]
+}
+
+/// The colour a fraction `t` of the way along `colours`, blending between
+/// neighbouring entries, with `t` clamped to `0..=1`.
+pub fn gradient(colours: &[[u8; 4]], t: f32) -> [u8; 4] {
+    let x = t.clamp(0.0, 1.0) * (colours.len() - 1) as f32;
+    let k = (x as usize).min(colours.len() - 1);
+    let (a, b) = (colours[k], colours[(k + 1).min(colours.len() - 1)]);
+    let f = x - k as f32;
+    let mut rgba = [0; 4];
+    for (c, (a, b)) in rgba.iter_mut().zip(a.iter().zip(b.iter())) {
+        *c = (*a as f32 + f * (*b as f32 - *a as f32)).round() as u8;
+    }
+    rgba
+}
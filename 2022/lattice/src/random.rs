@@ -0,0 +1,405 @@
+//! Interchangeable sources of random numbers, good and bad, for watching how
+//! a generator's flaws show up in a lattice.
+//!
+//! Every source hands out numbers in `[0, 1)`. The quasi-random sources
+//! (Halton, Sobol) cycle through the coordinates of one low-discrepancy point
+//! per draw, so a Metropolis proposal, which takes four draws, gets one
+//! four-dimensional point. `report` measures how far a source is from
+//! independent uniform draws.
+
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use std::time::Instant;
+
+/// A stream of numbers in `[0, 1)`.
+pub trait RandomSource {
+    fn next_f32(&mut self) -> f32;
+
+    /// An index in `range`, scaled from `next_f32`.
+    fn next_index(&mut self, range: Range<usize>) -> usize {
+        let x = self.next_f32();
+        (range.start + (x * (range.end - range.start) as f32) as usize).min(range.end - 1)
+    }
+}
+
+impl RandomSource for Pcg64 {
+    fn next_f32(&mut self) -> f32 {
+        self.gen::<f32>()
+    }
+
+    /// Unbiased, and the draws the samplers took before they took sources.
+    fn next_index(&mut self, range: Range<usize>) -> usize {
+        self.gen_range(range)
+    }
+}
+
+/// The per-thread streams of a parallel sweep, stream `k` of `seed` for
+/// thread `k`, so a run is reproducible for a fixed thread count. `rngs` keeps
+/// them between sweeps and is only reseeded when the count changes; the
+/// caller puts them back when done.
+pub(crate) fn thread_streams(rngs: &mut Vec<Pcg64>, seed: u64, n_threads: usize) -> Vec<Pcg64> {
+    if rngs.len() != n_threads {
+        *rngs = (0..n_threads).map(|k| Pcg64::new(seed as u128, k as u128)).collect();
+    }
+    std::mem::take(rngs)
+}
+
+/// Top 24 bits of a 32-bit word, as a float below one.
+fn unit(x: u32) -> f32 {
+    (x >> 8) as f32 / (1u32 << 24) as f32
+}
+
+/// Marsaglia's 64-bit xorshift: fast and fine by eye, though it fails
+/// stricter test batteries.
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        // an all-zero state never leaves zero
+        XorShift { state: seed.max(1) }
+    }
+}
+
+impl RandomSource for XorShift {
+    fn next_f32(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        unit((self.state >> 32) as u32)
+    }
+}
+
+/// RANDU, `x <- 65539 x mod 2^31`: every three consecutive draws fall on one
+/// of 15 planes through the unit cube.
+pub struct Lcg {
+    state: u32,
+}
+
+impl Lcg {
+    pub fn new(seed: u64) -> Self {
+        // RANDU needs an odd seed
+        Lcg { state: (seed as u32 & 0x7fff_ffff) | 1 }
+    }
+}
+
+impl RandomSource for Lcg {
+    fn next_f32(&mut self) -> f32 {
+        self.state = self.state.wrapping_mul(65539) & 0x7fff_ffff;
+        unit(self.state << 1)
+    }
+}
+
+/// The cosine of the nanoseconds since the source was made. Draws taken
+/// close together in time are close in value, and runs can't be repeated.
+pub struct Clock {
+    now: Instant,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Clock { now: Instant::now() }
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::new()
+    }
+}
+
+impl RandomSource for Clock {
+    fn next_f32(&mut self) -> f32 {
+        let duration = self.now.elapsed().as_nanos() as f32;
+        let x = (duration.cos() + 1.0) / 2.0;
+        x.min(1.0 - f32::EPSILON)
+    }
+}
+
+/// Number of coordinates per quasi-random point, one per draw of a Metropolis proposal.
+pub const QUASI_DIMENSIONS: usize = 4;
+
+const HALTON_BASES: [u64; QUASI_DIMENSIONS] = [2, 3, 5, 7];
+
+/// Halton points, with the radical inverse of the point index in the first
+/// four prime bases as coordinates.
+pub struct Halton {
+    index: u64,
+    dimension: usize,
+}
+
+impl Halton {
+    /// Starts `seed` points into the sequence.
+    pub fn new(seed: u64) -> Self {
+        Halton { index: seed + 1, dimension: 0 }
+    }
+}
+
+impl RandomSource for Halton {
+    fn next_f32(&mut self) -> f32 {
+        let base = HALTON_BASES[self.dimension];
+        let (mut n, mut scale, mut x) = (self.index, 1.0 / base as f64, 0.0);
+        while n > 0 {
+            x += (n % base) as f64 * scale;
+            n /= base;
+            scale /= base as f64;
+        }
+        self.dimension += 1;
+        if self.dimension == QUASI_DIMENSIONS {
+            self.dimension = 0;
+            self.index += 1;
+        }
+        (x as f32).min(1.0 - f32::EPSILON)
+    }
+}
+
+/// Degree, inner coefficients and initial direction numbers of Sobol
+/// dimensions 2 to 4, from Joe and Kuo's table. The first dimension is the
+/// van der Corput sequence in base 2.
+const SOBOL_POLYNOMIALS: [(usize, u32, [u32; 3]); QUASI_DIMENSIONS - 1] =
+    [(1, 0, [1, 0, 0]), (2, 1, [1, 3, 0]), (3, 1, [1, 3, 1])];
+
+const SOBOL_BITS: usize = 32;
+
+/// Sobol points, stepped in Gray-code order so each point differs from the
+/// last in one direction number per coordinate.
+pub struct Sobol {
+    directions: [[u32; SOBOL_BITS]; QUASI_DIMENSIONS],
+    point: [u32; QUASI_DIMENSIONS],
+    index: u64,
+    dimension: usize,
+}
+
+impl Sobol {
+    /// Starts `seed` points into the sequence, past the all-zero first point.
+    pub fn new(seed: u64) -> Self {
+        let mut directions = [[0u32; SOBOL_BITS]; QUASI_DIMENSIONS];
+        for (k, v) in directions[0].iter_mut().enumerate() {
+            *v = 1 << (SOBOL_BITS - 1 - k);
+        }
+        for (d, &(s, a, m)) in SOBOL_POLYNOMIALS.iter().enumerate() {
+            let v = &mut directions[d + 1];
+            for k in 0..s {
+                v[k] = m[k] << (SOBOL_BITS - 1 - k);
+            }
+            for k in s..SOBOL_BITS {
+                v[k] = v[k - s] ^ (v[k - s] >> s);
+                for l in 1..s {
+                    if (a >> (s - 1 - l)) & 1 == 1 {
+                        v[k] ^= v[k - l];
+                    }
+                }
+            }
+        }
+
+        // jump straight to the point of the starting index
+        let index = seed + 1;
+        let gray = index ^ (index >> 1);
+        let mut point = [0u32; QUASI_DIMENSIONS];
+        for (x, v) in point.iter_mut().zip(directions.iter()) {
+            for (k, &direction) in v.iter().enumerate() {
+                if (gray >> k) & 1 == 1 {
+                    *x ^= direction;
+                }
+            }
+        }
+        Sobol { directions, point, index, dimension: 0 }
+    }
+}
+
+impl RandomSource for Sobol {
+    fn next_f32(&mut self) -> f32 {
+        let x = unit(self.point[self.dimension]);
+        self.dimension += 1;
+        if self.dimension == QUASI_DIMENSIONS {
+            // the Gray code of the next index flips the bit of the lowest zero of this one
+            let k = (self.index.trailing_ones() as usize).min(SOBOL_BITS - 1);
+            for (x, v) in self.point.iter_mut().zip(self.directions.iter()) {
+                *x ^= v[k];
+            }
+            self.dimension = 0;
+            self.index += 1;
+        }
+        x
+    }
+}
+
+/// The kinds of source a sketch can pick at launch.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Generator {
+    /// The `Pcg64` stream the lattices use everywhere else.
+    Pcg,
+    XorShift,
+    /// RANDU.
+    Lcg,
+    /// The cosine of the elapsed time.
+    Time,
+    Halton,
+    Sobol,
+}
+
+impl Generator {
+    /// A fresh source of this kind. `Time` ignores the seed.
+    pub fn source(self, seed: u64) -> Box<dyn RandomSource + Send> {
+        match self {
+            Generator::Pcg => Box::new(Pcg64::seed_from_u64(seed)),
+            Generator::XorShift => Box::new(XorShift::new(seed)),
+            Generator::Lcg => Box::new(Lcg::new(seed)),
+            Generator::Time => Box::new(Clock::new()),
+            Generator::Halton => Box::new(Halton::new(seed)),
+            Generator::Sobol => Box::new(Sobol::new(seed)),
+        }
+    }
+}
+
+impl FromStr for Generator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pcg" => Ok(Generator::Pcg),
+            "xorshift" => Ok(Generator::XorShift),
+            "lcg" => Ok(Generator::Lcg),
+            "time" => Ok(Generator::Time),
+            "halton" => Ok(Generator::Halton),
+            "sobol" => Ok(Generator::Sobol),
+            _ => Err(format!("unknown generator {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Generator::Pcg => "pcg",
+            Generator::XorShift => "xorshift",
+            Generator::Lcg => "lcg",
+            Generator::Time => "time",
+            Generator::Halton => "halton",
+            Generator::Sobol => "sobol",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How a run of draws compares with independent uniform numbers.
+#[derive(Copy, Clone, Debug)]
+pub struct Report {
+    pub n_samples: usize,
+    pub n_bins: usize,
+    /// Pearson's chi-square of the bin counts against a flat histogram,
+    /// which averages `n_bins - 1` for a uniform source.
+    pub chi_square: f64,
+    /// Correlation between each draw and the next, which stays within about
+    /// `2 / sqrt(n_samples)` of zero for independent draws.
+    pub serial_correlation: f64,
+}
+
+impl Report {
+    /// Standard deviations the chi-square sits above its expected value.
+    pub fn chi_square_z(&self) -> f64 {
+        let dof = (self.n_bins - 1) as f64;
+        (self.chi_square - dof) / (2.0 * dof).sqrt()
+    }
+
+    /// Standard deviations the serial correlation sits from zero.
+    pub fn serial_z(&self) -> f64 {
+        self.serial_correlation * (self.n_samples as f64).sqrt()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "chi-square {:.1} over {} bins (z {:.1}), serial correlation {:.4} (z {:.1})",
+            self.chi_square,
+            self.n_bins,
+            self.chi_square_z(),
+            self.serial_correlation,
+            self.serial_z()
+        )
+    }
+}
+
+/// Draw `n_samples` numbers from `source` and measure their uniformity and
+/// the correlation between neighbours in the stream.
+pub fn report<R: RandomSource + ?Sized>(source: &mut R, n_samples: usize, n_bins: usize) -> Report {
+    assert!(n_samples > 1 && n_bins > 1, "need at least two samples and two bins");
+    let draws: Vec<f64> = (0..n_samples).map(|_| source.next_f32() as f64).collect();
+
+    // uniformity
+    let mut counts = vec![0usize; n_bins];
+    for &x in draws.iter() {
+        counts[((x * n_bins as f64) as usize).min(n_bins - 1)] += 1;
+    }
+    let expected = n_samples as f64 / n_bins as f64;
+    let chi_square = counts.iter().map(|&c| (c as f64 - expected).powi(2) / expected).sum();
+
+    // lag-one correlation
+    let mean = draws.iter().sum::<f64>() / n_samples as f64;
+    let variance: f64 = draws.iter().map(|x| (x - mean).powi(2)).sum();
+    let covariance: f64 = draws.windows(2).map(|w| (w[0] - mean) * (w[1] - mean)).sum();
+    let serial_correlation = if variance > 0.0 { covariance / variance } else { 1.0 };
+
+    Report { n_samples, n_bins, chi_square, serial_correlation }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points<R: RandomSource>(source: &mut R, n_points: usize) -> Vec<[f32; QUASI_DIMENSIONS]> {
+        (0..n_points)
+            .map(|_| {
+                let mut point = [0.0; QUASI_DIMENSIONS];
+                for x in point.iter_mut() {
+                    *x = source.next_f32();
+                }
+                point
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: &[[f32; QUASI_DIMENSIONS]], expected: &[[f32; QUASI_DIMENSIONS]]) {
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            for (x, y) in a.iter().zip(e.iter()) {
+                assert!((x - y).abs() < 1e-6, "{:?} against {:?}", actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn halton_starts_with_the_radical_inverses_of_one_two_three() {
+        let expected = [
+            [1.0 / 2.0, 1.0 / 3.0, 1.0 / 5.0, 1.0 / 7.0],
+            [1.0 / 4.0, 2.0 / 3.0, 2.0 / 5.0, 2.0 / 7.0],
+            [3.0 / 4.0, 1.0 / 9.0, 3.0 / 5.0, 3.0 / 7.0],
+        ];
+        assert_close(&points(&mut Halton::new(0), 3), &expected);
+    }
+
+    #[test]
+    fn sobol_starts_past_zero_as_joe_and_kuo_list_it() {
+        // their table steps in Gray-code order too
+        let expected = [
+            [0.5, 0.5, 0.5, 0.5],
+            [0.75, 0.25, 0.25, 0.25],
+            [0.25, 0.75, 0.75, 0.75],
+            [0.375, 0.375, 0.625, 0.875],
+        ];
+        assert_close(&points(&mut Sobol::new(0), 4), &expected);
+    }
+
+    #[test]
+    fn quasi_seeds_skip_into_the_sequence() {
+        let stepped = points(&mut Sobol::new(0), 12);
+        assert_close(&points(&mut Sobol::new(9), 3), &stepped[9..]);
+        let stepped = points(&mut Halton::new(0), 12);
+        assert_close(&points(&mut Halton::new(9), 3), &stepped[9..]);
+    }
+}
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Update scheme used by `LatticeModel::sweep`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Sampler {
+    /// Single-site proposals of a uniformly random state.
+    Metropolis,
+    /// Single-cluster flips, grown until a lattice's worth of sites has been flipped.
+    Wolff,
+    /// Multi-cluster relabelling of the whole lattice in one pass.
+    SwendsenWang,
+    /// Metropolis on the two checkerboard sublattices in turn, split across threads.
+    Checkerboard,
+    /// Single-site draws from the exact conditional distribution over all states.
+    HeatBath,
+    /// Swaps of unlike neighbours, which keep the count of every state fixed.
+    Kawasaki,
+}
+
+impl FromStr for Sampler {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "metropolis" => Ok(Sampler::Metropolis),
+            "wolff" => Ok(Sampler::Wolff),
+            "swendsen-wang" => Ok(Sampler::SwendsenWang),
+            "checkerboard" => Ok(Sampler::Checkerboard),
+            "heat-bath" => Ok(Sampler::HeatBath),
+            "kawasaki" => Ok(Sampler::Kawasaki),
+            _ => Err(format!("unknown sampler {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Sampler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Sampler::Metropolis => "metropolis",
+            Sampler::Wolff => "wolff",
+            Sampler::SwendsenWang => "swendsen-wang",
+            Sampler::Checkerboard => "checkerboard",
+            Sampler::HeatBath => "heat-bath",
+            Sampler::Kawasaki => "kawasaki",
+        };
+        write!(f, "{}", name)
+    }
+}
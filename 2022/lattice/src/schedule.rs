@@ -0,0 +1,274 @@
+//! Beta as a function of the step, built from keyframes.
+//!
+//! Between two keyframes beta follows the shape set on the first of them.
+//! A looping schedule starts over at its last keyframe, which must have the
+//! beta of its first, so frame `period` is exactly frame 0 and a captured
+//! loop plays seamlessly.
+//!
+//! Schedules can be read from a text file with one keyframe per line, the
+//! step, the beta, and optionally the shape towards the next keyframe, with
+//! a closing `loop` line for looping schedules:
+//!
+//! ```text
+//! # step beta shape
+//! 0    0.22  cosine
+//! 100  0.66  quench:5
+//! 200  0.22
+//! loop
+//! ```
+
+use std::f32::consts::PI;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// How beta moves from one keyframe to the next.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape {
+    /// Even steps in beta.
+    Linear,
+    /// Even ratios in beta, so even steps in temperature's logarithm.
+    Geometric,
+    /// Eased in and out along half a cosine.
+    Cosine,
+    /// Most of the way in the first few steps, then settling, with beta
+    /// covering `1 - exp(-rate * s)` of the distance at fraction `s` before
+    /// being scaled to land exactly.
+    Quench(f32),
+}
+
+impl Shape {
+    /// Beta a fraction `s` of the way from `from` to `to`.
+    pub fn interpolate(&self, from: f32, to: f32, s: f32) -> f32 {
+        match *self {
+            Shape::Linear => from + (to - from) * s,
+            Shape::Geometric => from * (to / from).powf(s),
+            Shape::Cosine => from + (to - from) * 0.5 * (1.0 - (PI * s).cos()),
+            Shape::Quench(rate) => from + (to - from) * (-rate * s).exp_m1() / (-rate).exp_m1(),
+        }
+    }
+}
+
+impl FromStr for Shape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None => match s {
+                "linear" => Ok(Shape::Linear),
+                "geometric" => Ok(Shape::Geometric),
+                "cosine" => Ok(Shape::Cosine),
+                "quench" => Ok(Shape::Quench(DEFAULT_QUENCH_RATE)),
+                _ => Err(format!("unknown shape {:?}", s)),
+            },
+            Some(("quench", rate)) => match rate.parse::<f32>() {
+                Ok(rate) if rate > 0.0 => Ok(Shape::Quench(rate)),
+                _ => Err(format!("invalid quench rate {:?}", rate)),
+            },
+            Some(_) => Err(format!("unknown shape {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Shape::Linear => write!(f, "linear"),
+            Shape::Geometric => write!(f, "geometric"),
+            Shape::Cosine => write!(f, "cosine"),
+            Shape::Quench(rate) => write!(f, "quench:{}", rate),
+        }
+    }
+}
+
+/// Rate of a `quench` given without one.
+pub const DEFAULT_QUENCH_RATE: f32 = 5.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Keyframe {
+    pub step: usize,
+    pub beta: f32,
+    /// Shape of the segment towards the next keyframe.
+    pub shape: Shape,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+    keyframes: Vec<Keyframe>,
+    looping: bool,
+}
+
+impl Schedule {
+    /// Keyframes in increasing step order. A looping schedule needs at least
+    /// two, the last with the first's beta.
+    pub fn new(keyframes: Vec<Keyframe>, looping: bool) -> Result<Self, String> {
+        let (first, last) = match (keyframes.first(), keyframes.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err("a schedule needs at least one keyframe".to_string()),
+        };
+        if first.step != 0 {
+            return Err(format!("first keyframe is at step {}, not 0", first.step));
+        }
+        for pair in keyframes.windows(2) {
+            if pair[1].step <= pair[0].step {
+                return Err(format!("keyframe at step {} comes after step {}", pair[1].step, pair[0].step));
+            }
+            if pair[0].shape == Shape::Geometric && (pair[0].beta <= 0.0 || pair[1].beta <= 0.0) {
+                return Err(format!("geometric segment from step {} needs positive betas", pair[0].step));
+            }
+        }
+        if looping && (keyframes.len() < 2 || last.beta != first.beta) {
+            return Err(format!("loop ends at beta {} instead of its starting {}", last.beta, first.beta));
+        }
+        Ok(Schedule { keyframes, looping })
+    }
+
+    /// From `start` to `end` over `n_steps`, then holding at `end`.
+    pub fn ramp(shape: Shape, start: f32, end: f32, n_steps: usize) -> Self {
+        let keyframes = vec![
+            Keyframe { step: 0, beta: start, shape },
+            Keyframe { step: n_steps.max(1), beta: end, shape },
+        ];
+        Schedule::new(keyframes, false).unwrap()
+    }
+
+    /// From `start` to `end` over the first half of `period` and back over
+    /// the second, over and over.
+    pub fn cycle(shape: Shape, start: f32, end: f32, period: usize) -> Self {
+        let period = period.max(2);
+        let keyframes = vec![
+            Keyframe { step: 0, beta: start, shape },
+            Keyframe { step: period / 2, beta: end, shape },
+            Keyframe { step: period, beta: start, shape },
+        ];
+        Schedule::new(keyframes, true).unwrap()
+    }
+
+    /// Read a schedule file in the format above.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    /// Steps until a looping schedule repeats.
+    pub fn period(&self) -> Option<usize> {
+        if self.looping {
+            self.keyframes.last().map(|last| last.step)
+        } else {
+            None
+        }
+    }
+
+    /// Beta at `step`. Past the last keyframe a looping schedule wraps round
+    /// and any other holds the last beta.
+    pub fn at(&self, step: usize) -> f32 {
+        let step = match self.period() {
+            Some(period) => step % period,
+            None => step,
+        };
+        let next = self.keyframes.partition_point(|k| k.step <= step);
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].beta;
+        }
+        let (from, to) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let s = (step - from.step) as f32 / (to.step - from.step) as f32;
+        from.shape.interpolate(from.beta, to.beta, s)
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    /// Keyframes on separate lines or separated by `;`, with `#` comments.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keyframes = Vec::new();
+        let mut looping = false;
+        for line in s.split(['\n', ';']) {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line == "loop" {
+                looping = true;
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 2 || fields.len() > 3 {
+                return Err(format!("expected `step beta [shape]`, got {:?}", line));
+            }
+            let step = fields[0].parse().map_err(|_| format!("invalid step {:?}", fields[0]))?;
+            let beta = fields[1].parse().map_err(|_| format!("invalid beta {:?}", fields[1]))?;
+            let shape = match fields.get(2) {
+                Some(shape) => shape.parse()?,
+                None => Shape::Linear,
+            };
+            keyframes.push(Keyframe { step, beta, shape });
+        }
+        Schedule::new(keyframes, looping)
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keyframes: Vec<String> =
+            self.keyframes.iter().map(|k| format!("{} {} {}", k.step, k.beta, k.shape)).collect();
+        write!(f, "{}", keyframes.join("; "))?;
+        if self.looping {
+            write!(f, "; loop")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycles_come_back_exactly() {
+        for &shape in [Shape::Linear, Shape::Geometric, Shape::Cosine, Shape::Quench(5.0)].iter() {
+            let schedule = Schedule::cycle(shape, 0.2, 0.6, 100);
+            assert_eq!(schedule.period(), Some(100));
+            assert_eq!(schedule.at(0), 0.2);
+            assert_eq!(schedule.at(50), 0.6);
+            assert_eq!(schedule.at(100), schedule.at(0));
+            for step in 0..100 {
+                assert_eq!(schedule.at(step + 300), schedule.at(step), "{} at step {}", shape, step);
+            }
+        }
+    }
+
+    #[test]
+    fn loops_wrap_from_their_last_keyframe() {
+        let schedule: Schedule = "0 0.2 cosine; 30 0.5 quench:3; 80 0.2; loop".parse().unwrap();
+        assert_eq!(schedule.period(), Some(80));
+        assert_eq!(schedule.at(30), 0.5);
+        assert_eq!(schedule.at(80), 0.2);
+        assert_eq!(schedule.at(110), 0.5);
+        assert_eq!(schedule.at(79 + 80), schedule.at(79));
+    }
+
+    #[test]
+    fn ramps_hold_at_the_end() {
+        let schedule = Schedule::ramp(Shape::Linear, 0.1, 0.5, 40);
+        assert_eq!(schedule.period(), None);
+        assert!((schedule.at(20) - 0.3).abs() < 1e-6);
+        assert_eq!(schedule.at(40), 0.5);
+        assert_eq!(schedule.at(1000), 0.5);
+    }
+
+    #[test]
+    fn loops_must_close() {
+        assert!("0 0.2; 50 0.6; loop".parse::<Schedule>().is_err());
+        assert!("0 0.2; loop".parse::<Schedule>().is_err());
+        assert!("10 0.2; 50 0.2; loop".parse::<Schedule>().is_err());
+        assert!("0 0.2; 50 0.6; 40 0.2; loop".parse::<Schedule>().is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use crate::model::LatticeModel;
+use crate::random::RandomSource;
+
+/// Root of `x`'s tree, halving the path on the way up.
+pub(crate) fn find(parent: &mut [usize], mut x: usize) -> usize {
+    while parent[x] != x {
+        parent[x] = parent[parent[x]];
+        x = parent[x];
+    }
+    x
+}
+
+impl LatticeModel {
+    /// One Swendsen–Wang update: activate bonds between equal neighbours,
+    /// then give every resulting cluster a uniformly random state.
+    ///
+    /// A bond is active with probability `1 - exp(-2 beta j)`, so every coupling
+    /// must be non-negative; with a per-site beta the two endpoints are
+    /// averaged. A fixed border and the external field act as one extra node,
+    /// and the clusters bonded to it keep their state.
+    pub fn swendsen_wang_sweep(&mut self) {
+        self.with_own_rng(|lattice, rng| lattice.swendsen_wang_sweep_with(rng));
+    }
+
+    /// `swendsen_wang_sweep` drawing from `source`.
+    pub fn swendsen_wang_sweep_with<R: RandomSource + ?Sized>(&mut self, source: &mut R) {
+        assert!(self.couplings.is_ferromagnetic(), "Swendsen-Wang clusters need non-negative couplings, not {}", self.couplings);
+        let (n_x, n_y) = (self.n_x, self.n_y);
+        let index = |i: usize, j: usize| i + j * n_x;
+        let anchor = n_x * n_y;
+        let mut parent: Vec<usize> = (0..=anchor).collect();
+
+        // build bond clusters, trying each bond once from its lower-indexed end
+        for j in 0..n_y {
+            for i in 0..n_x {
+                let aij = self.a[[i, j]];
+                let nn = self.neighbors(i, j);
+                let p_anchor = self.anchor_probability(i, j, aij, &nn);
+                if p_anchor > 0.0 && source.next_f32() < p_anchor {
+                    let root = find(&mut parent, anchor);
+                    let other = find(&mut parent, index(i, j));
+                    parent[other] = root;
+                }
+                for ((ni, nj), coupling) in nn.bonds() {
+                    if index(ni, nj) <= index(i, j) || self.a[[ni, nj]] != aij {
+                        continue;
+                    }
+                    let beta = 0.5 * (self.beta.at(i, j) + self.beta.at(ni, nj));
+                    let p_bond = 1.0 - (-2.0 * beta * coupling).exp();
+                    if source.next_f32() < p_bond {
+                        let root = find(&mut parent, index(i, j));
+                        let other = find(&mut parent, index(ni, nj));
+                        parent[other] = root;
+                    }
+                }
+            }
+        }
+
+        // relabel each cluster, drawing its state when its root is first seen
+        let mut states: Vec<i8> = vec![-1; anchor + 1];
+        let anchored = find(&mut parent, anchor);
+        for j in 0..n_y {
+            for i in 0..n_x {
+                let root = find(&mut parent, index(i, j));
+                if root == anchored {
+                    continue;
+                }
+                if states[root] < 0 {
+                    states[root] = source.next_index(0..self.n_states) as i8;
+                }
+                self.a[[i, j]] = states[root];
+            }
+        }
+    }
+}
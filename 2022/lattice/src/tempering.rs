@@ -0,0 +1,106 @@
+//! Parallel tempering: replicas of one lattice held at a ladder of betas,
+//! with neighbouring replicas trading configurations now and then so cold
+//! replicas borrow the fast mixing of hot ones.
+
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use std::thread;
+
+use crate::model::{Beta, LatticeModel};
+use crate::observables::Observables;
+
+/// Betas from `beta_start` to `beta_end` in constant ratios, which keeps swap
+/// rates roughly even when the specific heat doesn't change much.
+pub fn geometric_ladder(beta_start: f32, beta_end: f32, n_replicas: usize) -> Vec<f32> {
+    if n_replicas < 2 {
+        return vec![beta_start; n_replicas];
+    }
+    let ratio = (beta_end / beta_start).powf(1.0 / (n_replicas - 1) as f32);
+    (0..n_replicas).map(|k| beta_start * ratio.powi(k as i32)).collect()
+}
+
+/// Replica `k` keeps its own global beta; swaps move configurations, not
+/// betas, so a sketch can draw replica `k` in the same panel every frame.
+pub struct Tempering {
+    pub replicas: Vec<LatticeModel>,
+    /// Sweeps between rounds of exchange attempts.
+    pub exchange_every: usize,
+    /// Accepted and attempted swaps between replica `k` and `k + 1`.
+    pub accepted: Vec<usize>,
+    pub attempted: Vec<usize>,
+    pub rng: Pcg64,
+    since_exchange: usize,
+    n_exchanges: usize,
+}
+
+impl Tempering {
+    /// One lattice per beta, each seeded from `seed` plus its position on the ladder.
+    pub fn new(n_x: usize, n_y: usize, n_states: usize, betas: &[f32], seed: u64) -> Self {
+        let replicas = betas
+            .iter()
+            .enumerate()
+            .map(|(k, &beta)| LatticeModel::new(n_x, n_y, n_states, Beta::Global(beta), seed + 1 + k as u64))
+            .collect();
+        let n_pairs = betas.len().saturating_sub(1);
+        Tempering {
+            replicas,
+            exchange_every: 1,
+            accepted: vec![0; n_pairs],
+            attempted: vec![0; n_pairs],
+            rng: Pcg64::seed_from_u64(seed),
+            since_exchange: 0,
+            n_exchanges: 0,
+        }
+    }
+
+    /// Beta of each replica, from the first rung to the last.
+    pub fn betas(&self) -> Vec<f32> {
+        self.replicas.iter().map(|lattice| lattice.beta.mean()).collect()
+    }
+
+    /// Sweep every replica, each on its own thread, then try exchanges if due.
+    pub fn sweep(&mut self) {
+        thread::scope(|s| {
+            for lattice in self.replicas.iter_mut() {
+                s.spawn(move || lattice.sweep());
+            }
+        });
+        self.since_exchange += 1;
+        if self.exchange_every > 0 && self.since_exchange >= self.exchange_every {
+            self.since_exchange = 0;
+            self.exchange();
+        }
+    }
+
+    /// Try to swap neighbouring replicas, alternating between the pairs that
+    /// start on even and on odd rungs. A swap is kept with probability
+    /// `min(1, exp((beta_k - beta_k+1) * (E_k - E_k+1)))`.
+    pub fn exchange(&mut self) {
+        let betas = self.betas();
+        let energies: Vec<f32> = self
+            .replicas
+            .iter()
+            .map(|lattice| lattice.energy_per_site() * lattice.n_sites() as f32)
+            .collect();
+        let first = self.n_exchanges % 2;
+        self.n_exchanges += 1;
+        for k in (first..self.replicas.len().saturating_sub(1)).step_by(2) {
+            let delta = (betas[k] - betas[k + 1]) * (energies[k] - energies[k + 1]);
+            self.attempted[k] += 1;
+            if self.rng.gen::<f32>().ln() < delta {
+                let (lower, upper) = self.replicas.split_at_mut(k + 1);
+                std::mem::swap(&mut lower[k].a, &mut upper[0].a);
+                self.accepted[k] += 1;
+            }
+        }
+    }
+
+    /// Fraction of accepted swaps per neighbouring pair so far.
+    pub fn acceptance(&self) -> Vec<f32> {
+        self.accepted
+            .iter()
+            .zip(self.attempted.iter())
+            .map(|(&a, &n)| if n > 0 { a as f32 / n as f32 } else { 0.0 })
+            .collect()
+    }
+}
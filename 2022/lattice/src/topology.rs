@@ -0,0 +1,160 @@
+//! Lattice connectivity and the positions of sites in the plane.
+//!
+//! Every topology is stored in the same `n_x` by `n_y` array. The triangular
+//! lattice shifts odd rows half a cell to the right; the honeycomb is stored
+//! as a brick wall, where each site links left, right and one of up or down.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+const SQRT_3: f32 = 1.732_050_8;
+
+/// Six triangular neighbours, or four square ones plus four diagonals.
+pub const MAX_NEIGHBORS: usize = 8;
+
+/// Sites bonded to one site and the coupling of each bond, without allocating.
+#[derive(Copy, Clone, Debug)]
+pub struct Neighbors {
+    sites: [(usize, usize); MAX_NEIGHBORS],
+    couplings: [f32; MAX_NEIGHBORS],
+    len: usize,
+    /// Total coupling of the bonds that left through an open or fixed edge.
+    pub border: f32,
+}
+
+impl Neighbors {
+    pub(crate) fn new() -> Self {
+        Neighbors {
+            sites: [(0, 0); MAX_NEIGHBORS],
+            couplings: [0.0; MAX_NEIGHBORS],
+            len: 0,
+            border: 0.0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, site: (usize, usize), coupling: f32) {
+        self.sites[self.len] = site;
+        self.couplings[self.len] = coupling;
+        self.len += 1;
+    }
+
+    /// Each neighbour with the coupling of its bond.
+    pub fn bonds(&self) -> impl Iterator<Item = ((usize, usize), f32)> + '_ {
+        self.iter().copied().zip(self.couplings[..self.len].iter().copied())
+    }
+}
+
+impl Deref for Neighbors {
+    type Target = [(usize, usize)];
+
+    fn deref(&self) -> &Self::Target {
+        &self.sites[..self.len]
+    }
+}
+
+/// Steps from one site to each of its neighbours, before any wrapping.
+#[derive(Copy, Clone, Debug)]
+pub struct Offsets {
+    steps: [(isize, isize); MAX_NEIGHBORS],
+    len: usize,
+}
+
+impl Offsets {
+    fn new(steps: &[(isize, isize)]) -> Self {
+        let mut offsets = Offsets { steps: [(0, 0); MAX_NEIGHBORS], len: steps.len() };
+        offsets.steps[..steps.len()].copy_from_slice(steps);
+        offsets
+    }
+}
+
+impl Deref for Offsets {
+    type Target = [(isize, isize)];
+
+    fn deref(&self) -> &Self::Target {
+        &self.steps[..self.len]
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Topology {
+    /// Four neighbours.
+    Square,
+    /// Six neighbours; wrapping needs an even `n_y`.
+    Triangular,
+    /// Three neighbours; wrapping needs even `n_x` and `n_y`.
+    Honeycomb,
+}
+
+impl Topology {
+    /// Steps to the nearest neighbours of `(i, j)`; the boundary decides where they land.
+    pub fn offsets(&self, i: usize, j: usize) -> Offsets {
+        match self {
+            Topology::Square => Offsets::new(&[(-1, 0), (1, 0), (0, -1), (0, 1)]),
+            Topology::Triangular => {
+                // the rows above and below overlap this cell and the one toward the shift
+                let side = if j % 2 == 1 { 1 } else { -1 };
+                Offsets::new(&[(-1, 0), (1, 0), (0, -1), (side, -1), (0, 1), (side, 1)])
+            }
+            Topology::Honeycomb => {
+                let vertical = if (i + j) % 2 == 1 { 1 } else { -1 };
+                Offsets::new(&[(-1, 0), (1, 0), (0, vertical)])
+            }
+        }
+    }
+
+    /// Centre of site `(i, j)` with nearest neighbours one unit apart.
+    pub fn position(&self, i: usize, j: usize) -> [f32; 2] {
+        let (x, y) = (i as f32, j as f32);
+        match self {
+            Topology::Square => [x + 0.5, y + 0.5],
+            Topology::Triangular => [x + 0.5 + 0.5 * (j % 2) as f32, (y + 0.5) * 0.5 * SQRT_3],
+            Topology::Honeycomb => {
+                // zig-zag rows, with the sites that bond upward raised half a unit
+                let raised = ((i + j) % 2) as f32;
+                [(x + 0.5) * 0.5 * SQRT_3, 1.5 * y + 0.5 + 0.5 * raised]
+            }
+        }
+    }
+
+    /// Width and height covered by an `n_x` by `n_y` lattice, in the units of `position`.
+    pub fn extent(&self, n_x: usize, n_y: usize) -> [f32; 2] {
+        let (x, y) = (n_x as f32, n_y as f32);
+        match self {
+            Topology::Square => [x, y],
+            Topology::Triangular => [x + 0.5, y * 0.5 * SQRT_3],
+            Topology::Honeycomb => [x * 0.5 * SQRT_3, 1.5 * y + 0.5],
+        }
+    }
+
+    /// Whether a checkerboard on `i + j` splits the lattice into two
+    /// sublattices that share no bonds.
+    pub fn is_bipartite(&self) -> bool {
+        !matches!(self, Topology::Triangular)
+    }
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "square" => Ok(Topology::Square),
+            "triangular" => Ok(Topology::Triangular),
+            "honeycomb" => Ok(Topology::Honeycomb),
+            _ => Err(format!("unknown topology {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Topology::Square => "square",
+            Topology::Triangular => "triangular",
+            Topology::Honeycomb => "honeycomb",
+        };
+        write!(f, "{}", name)
+    }
+}
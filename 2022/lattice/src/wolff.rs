@@ -0,0 +1,102 @@
+use crate::model::LatticeModel;
+use crate::random::RandomSource;
+
+/// Weight of the latest sweep in the running mean cluster size.
+const WOLFF_SIZE_DECAY: f32 = 0.05;
+
+impl LatticeModel {
+    /// Grow one Wolff cluster from a random site and move it to a random other
+    /// state. Returns the number of sites flipped.
+    ///
+    /// A bond joins the cluster with probability `1 - exp(-2 beta j)`, so every
+    /// coupling must be non-negative; with a per-site beta the two endpoints
+    /// are averaged. A cluster held by a fixed border or the external field is
+    /// left unchanged, though its sites still count toward the sweep; under a
+    /// strong field at low temperature most clusters are held, and Metropolis
+    /// or heat-bath mix faster.
+    pub fn wolff_step(&mut self) -> usize {
+        self.with_own_rng(|lattice, rng| lattice.wolff_step_with(rng))
+    }
+
+    /// `wolff_step` drawing from `source`.
+    pub fn wolff_step_with<R: RandomSource + ?Sized>(&mut self, source: &mut R) -> usize {
+        assert!(self.couplings.is_ferromagnetic(), "Wolff clusters need non-negative couplings, not {}", self.couplings);
+        // with a single state there's nothing to flip a cluster to
+        if self.n_states < 2 {
+            return 0;
+        }
+        let i: usize = source.next_index(0..self.n_x);
+        let j: usize = source.next_index(0..self.n_y);
+        let old_state = self.a[[i, j]];
+        let new_state = (old_state as usize + source.next_index(1..self.n_states)) % self.n_states;
+        let new_state = new_state as i8;
+
+        // sites are relabelled as they join, so nothing is visited twice
+        let mut stack = vec![(i, j)];
+        let mut cluster = vec![(i, j)];
+        self.a[[i, j]] = new_state;
+        let mut anchored = false;
+        while let Some((i, j)) = stack.pop() {
+            let nn = self.neighbors(i, j);
+            let p_anchor = self.anchor_probability(i, j, old_state, &nn);
+            if p_anchor > 0.0 && source.next_f32() < p_anchor {
+                anchored = true;
+            }
+            for ((ni, nj), coupling) in nn.bonds() {
+                if self.a[[ni, nj]] != old_state {
+                    continue;
+                }
+                let beta = 0.5 * (self.beta.at(i, j) + self.beta.at(ni, nj));
+                let p_add = 1.0 - (-2.0 * beta * coupling).exp();
+                if source.next_f32() < p_add {
+                    self.a[[ni, nj]] = new_state;
+                    stack.push((ni, nj));
+                    cluster.push((ni, nj));
+                }
+            }
+        }
+        if anchored {
+            for &site in cluster.iter() {
+                self.a[site] = old_state;
+            }
+        }
+        cluster.len()
+    }
+
+    /// Flip about `n_x * n_y` sites' worth of clusters.
+    ///
+    /// Stopping once that many sites have flipped would favour states left
+    /// by large clusters, so the number of clusters is fixed up front from a
+    /// slow running mean of the cluster size, which still follows a beta ramp.
+    pub fn wolff_sweep(&mut self) {
+        self.with_own_rng(|lattice, rng| lattice.wolff_sweep_with(rng));
+    }
+
+    /// `wolff_sweep` drawing from `source`.
+    pub fn wolff_sweep_with<R: RandomSource + ?Sized>(&mut self, source: &mut R) {
+        if self.n_states < 2 {
+            return;
+        }
+        let n_sites = self.n_x * self.n_y;
+        let mut n_flipped = 0;
+        let mut n_clusters = 0;
+        if self.wolff_mean_size > 0.0 {
+            let n_steps = (n_sites as f32 / self.wolff_mean_size).round().max(1.0) as usize;
+            for _ in 0..n_steps {
+                n_flipped += self.wolff_step_with(source);
+            }
+            n_clusters = n_steps;
+        } else {
+            while n_flipped < n_sites {
+                n_flipped += self.wolff_step_with(source);
+                n_clusters += 1;
+            }
+        }
+        let mean_size = n_flipped as f32 / n_clusters as f32;
+        if self.wolff_mean_size > 0.0 {
+            self.wolff_mean_size += WOLFF_SIZE_DECAY * (mean_size - self.wolff_mean_size);
+        } else {
+            self.wolff_mean_size = mean_size;
+        }
+    }
+}
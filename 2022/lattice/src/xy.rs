@@ -0,0 +1,224 @@
+//! XY model, with a planar spin angle per site, and its q-state clock discretisation.
+//!
+//! A bond costs `-j cos(theta_1 - theta_2)`. The XY model orders through the
+//! Kosterlitz–Thouless transition near `beta = 1.12`, where bound pairs of
+//! vortices and antivortices unbind.
+
+use ndarray::prelude::*;
+use ndarray::{Array, Ix2};
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use std::f32::consts::PI;
+
+use crate::boundary::Boundary;
+use crate::model::{bonds, Beta, Couplings};
+use crate::observables::Observables;
+use crate::topology::{Neighbors, Topology};
+
+/// Largest rotation an XY proposal makes by default, in radians.
+pub const DEFAULT_STEP: f32 = 1.0;
+
+/// Planar spins on a lattice. A fixed border points at the angle of its state
+/// on the clock, or along zero for continuous angles.
+pub struct XyModel {
+    pub n_x: usize,
+    pub n_y: usize,
+    /// Number of clock angles, or 0 for continuous angles.
+    pub n_clock: usize,
+    /// Spin angle per site, in `[0, 2 pi)`.
+    pub theta: Array<f32, Ix2>,
+    pub topology: Topology,
+    pub boundary: Boundary,
+    pub couplings: Couplings,
+    pub beta: Beta,
+    /// Largest rotation an XY proposal makes, in radians.
+    pub step: f32,
+    pub rng: Pcg64,
+}
+
+impl XyModel {
+    /// Continuous spins, all pointing along zero.
+    pub fn new(n_x: usize, n_y: usize, beta: Beta, seed: u64) -> Self {
+        XyModel {
+            n_x,
+            n_y,
+            n_clock: 0,
+            theta: Array::<f32, Ix2>::zeros((n_x, n_y).f()),
+            topology: Topology::Square,
+            boundary: Boundary::Periodic,
+            couplings: Couplings::default(),
+            beta,
+            step: DEFAULT_STEP,
+            rng: Pcg64::seed_from_u64(seed),
+        }
+    }
+
+    /// Spins restricted to `n_clock` evenly spaced angles, all pointing along
+    /// zero. Zero clock angles gives continuous spins, as `new` does.
+    pub fn clock(n_x: usize, n_y: usize, n_clock: usize, beta: Beta, seed: u64) -> Self {
+        XyModel { n_clock, ..XyModel::new(n_x, n_y, beta, seed) }
+    }
+
+    /// Point every site in a uniformly random allowed direction.
+    pub fn randomize(&mut self) {
+        for i in 0..self.n_x {
+            for j in 0..self.n_y {
+                self.theta[[i, j]] = self.random_angle();
+            }
+        }
+    }
+
+    fn random_angle(&mut self) -> f32 {
+        if self.n_clock > 0 {
+            let state = self.rng.gen_range(0..self.n_clock);
+            self.clock_angle(state)
+        } else {
+            self.rng.gen_range(0.0..2.0 * PI)
+        }
+    }
+
+    fn clock_angle(&self, state: usize) -> f32 {
+        2.0 * PI * state as f32 / self.n_clock as f32
+    }
+
+    /// Use `boundary`, panicking if it pins the border to a clock state the
+    /// model doesn't have. Continuous spins only take `fixed:0`, along zero.
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        if let Some(state) = boundary.fixed_state() {
+            let n_states = self.n_clock.max(1);
+            assert!(
+                0 <= state && (state as usize) < n_states,
+                "a fixed border needs a state from 0 to {}, not {}",
+                n_states - 1,
+                state
+            );
+        }
+        self.boundary = boundary;
+    }
+
+    /// Angle the border holds under a fixed boundary.
+    fn border_angle(&self, state: i8) -> f32 {
+        if self.n_clock > 0 {
+            self.clock_angle(state as usize)
+        } else {
+            0.0
+        }
+    }
+
+    /// The sites bonded to `(i, j)`, as in `LatticeModel::neighbors`.
+    pub fn neighbors(&self, i: usize, j: usize) -> Neighbors {
+        bonds(self.topology, self.boundary, &self.couplings, self.n_x, self.n_y, i, j)
+    }
+
+    /// Energy of the bonds of site `(i, j)`.
+    pub fn compute_energy(&self, i: usize, j: usize) -> f32 {
+        self.site_energy(i, j, self.theta[[i, j]])
+    }
+
+    /// Energy site `(i, j)` would have pointing along `theta`, leaving the lattice untouched.
+    pub fn site_energy(&self, i: usize, j: usize, theta: f32) -> f32 {
+        let (shared, own) = self.split_energy(i, j, theta);
+        shared + own
+    }
+
+    /// Energy of the bonds to other sites, and of the bonds to a fixed border.
+    fn split_energy(&self, i: usize, j: usize, theta: f32) -> (f32, f32) {
+        let nn = self.neighbors(i, j);
+        let mut shared = 0.0;
+        for (n, coupling) in nn.bonds() {
+            shared -= coupling * (theta - self.theta[n]).cos();
+        }
+        let own = match self.boundary.fixed_state() {
+            Some(state) => -nn.border * (theta - self.border_angle(state)).cos(),
+            None => 0.0,
+        };
+        (shared, own)
+    }
+
+    /// Propose `new_theta` at `(i, j)` and keep it unless `ln(u)` exceeds
+    /// `beta * (E_old - E_new)`. Returns whether the move was accepted.
+    pub fn metropolis(&mut self, i: usize, j: usize, new_theta: f32, u: f32) -> bool {
+        // compute energy
+        let old_energy = self.compute_energy(i, j);
+        let new_energy = self.site_energy(i, j, new_theta);
+
+        // flip a coin and reject if condition is met
+        if u.ln() > self.beta.at(i, j) * (old_energy - new_energy) {
+            return false;
+        }
+        self.theta[[i, j]] = new_theta;
+        true
+    }
+
+    /// `n_x * n_y` Metropolis proposals at random sites. Clock spins jump to
+    /// a random angle; XY spins turn by up to `step` either way.
+    pub fn sweep(&mut self) {
+        for _ in 0..(self.n_x * self.n_y) {
+            let i: usize = self.rng.gen_range(0..self.n_x);
+            let j: usize = self.rng.gen_range(0..self.n_y);
+            let new_theta = if self.n_clock > 0 {
+                self.random_angle()
+            } else {
+                let turn = self.rng.gen_range(-self.step..self.step);
+                (self.theta[[i, j]] + turn).rem_euclid(2.0 * PI)
+            };
+            let u = self.rng.gen::<f32>();
+            self.metropolis(i, j, new_theta, u);
+        }
+    }
+
+    /// Winding number around the square plaquette with `(i, j)` at one corner
+    /// and `(i + 1, j + 1)` at the other: `1` for a vortex, `-1` for an
+    /// antivortex, `0` otherwise. Plaquettes cut by an open or fixed edge have none.
+    pub fn vorticity(&self, i: usize, j: usize) -> i32 {
+        let corners = [(0, 0), (1, 0), (1, 1), (0, 1)];
+        let mut angles = [0.0; 4];
+        for (angle, &(di, dj)) in angles.iter_mut().zip(corners.iter()) {
+            let (x, y) = (i as isize + di, j as isize + dj);
+            match self.boundary.resolve(x, y, self.n_x, self.n_y) {
+                Some(site) => *angle = self.theta[site],
+                None => return 0,
+            }
+        }
+
+        // sum the turns between corners, each taken the short way round
+        let mut winding = 0.0;
+        for k in 0..4 {
+            let delta = angles[(k + 1) % 4] - angles[k];
+            winding += (delta + PI).rem_euclid(2.0 * PI) - PI;
+        }
+        (winding / (2.0 * PI)).round() as i32
+    }
+}
+
+impl Observables for XyModel {
+    fn n_sites(&self) -> usize {
+        self.n_x * self.n_y
+    }
+
+    fn mean_beta(&self) -> f32 {
+        self.beta.mean()
+    }
+
+    /// Length of the mean spin vector.
+    fn magnetization(&self) -> f32 {
+        let (mut sum_x, mut sum_y) = (0.0, 0.0);
+        for &theta in self.theta.iter() {
+            sum_x += theta.cos();
+            sum_y += theta.sin();
+        }
+        (sum_x * sum_x + sum_y * sum_y).sqrt() / self.n_sites() as f32
+    }
+
+    fn energy_per_site(&self) -> f32 {
+        let mut energy = 0.0;
+        for i in 0..self.n_x {
+            for j in 0..self.n_y {
+                // bonds between sites are counted from both ends, the rest once
+                let (shared, own) = self.split_energy(i, j, self.theta[[i, j]]);
+                energy += 0.5 * shared + own;
+            }
+        }
+        energy / self.n_sites() as f32
+    }
+}
@@ -0,0 +1,55 @@
+//! Drawing a glyph's marks with nannou.
+
+use lattice::glyph::{Glyph, Mark};
+use nannou::prelude::*;
+
+fn get_color(colours: &[[u8; 4]], index: usize) -> Rgba<u8> {
+    let [r, g, b, a] = colours[index];
+    rgba(r, g, b, a)
+}
+
+/// Draw the site in `state` as `glyph`, centred on `xy` in a cell `wh`
+/// across, with each mark in its colour from `colours` and strokes
+/// `stroke_weight` wide.
+pub fn draw_glyph(draw: &Draw, glyph: &dyn Glyph, colours: &[[u8; 4]], stroke_weight: f32, state: usize, xy: Point2, wh: Vec2) {
+    // glyphs come in cell units, centred on the site
+    let at = |p: [f32; 2]| pt2(xy.x + p[0] * wh.x, xy.y + p[1] * wh.y);
+    for mark in glyph.marks(state, colours.len()) {
+        match mark {
+            Mark::Stroke { points, closed: true, colour } => {
+                draw.polygon()
+                    .no_fill()
+                    .stroke_color(get_color(colours, colour))
+                    .stroke_weight(stroke_weight)
+                    .points(points.into_iter().map(at));
+            }
+            Mark::Stroke { points, closed: false, colour } => {
+                draw.polyline()
+                    .weight(stroke_weight)
+                    .color(get_color(colours, colour))
+                    .points(points.into_iter().map(at));
+            }
+            Mark::Fill { points, colour } => {
+                draw.polygon()
+                    .color(get_color(colours, colour))
+                    .points(points.into_iter().map(at));
+            }
+            Mark::Ellipse { centre, size, rotation, colour } => {
+                draw.ellipse()
+                    .color(get_color(colours, colour))
+                    .w(size[0] * wh.x)
+                    .h(size[1] * wh.y)
+                    .rotate(rotation)
+                    .xy(at(centre));
+            }
+            Mark::Mesh { vertices } => {
+                let points = vertices
+                    .into_iter()
+                    .map(|(p, colour)| (vec3(p[0] * wh.x, p[1] * wh.y, p[2] * wh.x), get_color(colours, colour)));
+                draw.translate(vec3(xy.x, xy.y, 0.0))
+                    .mesh()
+                    .points_colored(points);
+            }
+        }
+    }
+}
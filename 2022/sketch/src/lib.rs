@@ -0,0 +1,8 @@
+//! Nannou-side drawing shared by the lattice sketches.
+//!
+//! The lattice crate stays free of nannou; anything that turns its output
+//! into draw calls lives here instead of being copied into every day.
+
+pub mod glyph;
+
+pub use glyph::draw_glyph;
@@ -0,0 +1,178 @@
+use lattice::args::arg;
+use lattice::glyph::{self, Glyph};
+use lattice::tempering::geometric_ladder;
+use lattice::{Boundary, Palette, Recorder, Sampler, Tempering};
+use nannou::prelude::*;
+use sketch::draw_glyph;
+
+const SEED: u64 = 12345;
+const WINDOW_WIDTH: f32 = 600.0;
+const WINDOW_HEIGHT: f32 = 600.0;
+const SQUARE_WIDTH: f32 = 500.0;
+const SQUARE_HEIGHT: f32 = 500.0;
+const SPIN_WIDTH_X: f32 = 5.0;
+const SPIN_WIDTH_Y: f32 = 5.0;
+const PANEL_MARGIN: f32 = 12.0;
+const BETA_C: f32 = 0.5025262693711905; // (3.).sqrt().ln_1p() / 2., the 3-state Potts point
+const BETA_START: f32 = 0.5 * BETA_C;
+const BETA_END: f32 = 2.0 * BETA_C;
+const N_REPLICAS: usize = 9;
+const EXCHANGE_EVERY: usize = 1;
+const PALETTE: &str = "pink-light";
+const GLYPH: &str = "polygon";
+const STROKE_WEIGHT: f32 = 1.0;
+const SAMPLER: Sampler = Sampler::Metropolis;
+const BOUNDARY: Boundary = Boundary::Periodic;
+
+struct Model {
+    _window: window::Id,
+    w_x: f32,
+    w_y: f32,
+    x0: f32,
+    y1: f32,
+    panel_width: f32,
+    panel_height: f32,
+    n_cols: usize,
+    rgbas: Vec<[u8; 4]>,
+    glyph: Box<dyn Glyph>,
+    tempering: Tempering,
+    recorders: Vec<Recorder>,
+}
+
+fn model(app: &App) -> Model {
+    let _window = app
+        .new_window()
+        .size(WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32)
+        .view(view)
+        .build()
+        .unwrap();
+
+    let offset_x = (WINDOW_WIDTH - SQUARE_WIDTH) / 2.0;
+    let offset_y = (WINDOW_HEIGHT - SQUARE_HEIGHT) / 2.0;
+    let x0: f32 = offset_x - 0.5*WINDOW_WIDTH;
+    let y1: f32 = SQUARE_HEIGHT + offset_y - 0.5*WINDOW_HEIGHT;
+
+    // lay the replicas out in a near-square grid, hottest top left
+    let n_replicas: usize = arg("replicas").unwrap_or(N_REPLICAS);
+    let n_cols: usize = (n_replicas as f32).sqrt().ceil() as usize;
+    let n_rows: usize = (n_replicas + n_cols - 1) / n_cols;
+    let panel_width: f32 = SQUARE_WIDTH / n_cols as f32;
+    let panel_height: f32 = SQUARE_HEIGHT / n_rows as f32;
+
+    let w_x: f32 = SPIN_WIDTH_X;
+    let w_y: f32 = SPIN_WIDTH_Y;
+    let n_x: usize = (panel_width - 2.0 * PANEL_MARGIN) as usize / w_x as usize;
+    let n_y: usize = (panel_height - 2.0 * PANEL_MARGIN) as usize / w_y as usize;
+
+    // one state per colour, from a named palette, a palette file or a hex list
+    let palette: Palette = arg("palette").unwrap_or_else(|| PALETTE.parse().unwrap());
+    let n_states: usize = palette.len();
+    println!("palette {}, n_states {}", palette, n_states);
+    let rgbas: Vec<[u8; 4]> = palette.colours;
+
+    // how each site is drawn, by name or from SVG files as svg:a.svg,b.svg
+    let glyph_name: String = arg("glyph").unwrap_or_else(|| GLYPH.to_string());
+    let glyph: Box<dyn Glyph> = glyph::parse(&glyph_name).unwrap();
+    println!("glyph {}", glyph_name);
+
+    println!("w_x {}, w_y {}, n_x {}, n_y {}", w_x, w_y, n_x, n_y);
+    let betas = geometric_ladder(BETA_START, BETA_END, n_replicas);
+    let mut tempering = Tempering::new(n_x, n_y, n_states, &betas, SEED);
+    tempering.exchange_every = arg("exchange-every").unwrap_or(EXCHANGE_EVERY);
+    let sampler: Sampler = arg("sampler").unwrap_or(SAMPLER);
+    let boundary: Boundary = arg("boundary").unwrap_or(BOUNDARY);
+    for lattice in tempering.replicas.iter_mut() {
+        lattice.randomize();
+        lattice.sampler = sampler;
+        lattice.set_boundary(boundary);
+        lattice.couplings = arg("couplings").unwrap_or(lattice.couplings);
+    }
+    println!("replicas {}, exchange every {}, sampler {}, boundary {}", n_replicas, tempering.exchange_every, sampler, boundary);
+    println!("betas {:?}", betas);
+    let recorders: Vec<Recorder> = (0..n_replicas)
+        .map(|k| Recorder::create(observables_path(app, &format!("observables_{}", k))).unwrap())
+        .collect();
+
+    Model {
+        _window,
+        w_x,
+        w_y,
+        x0,
+        y1,
+        panel_width,
+        panel_height,
+        n_cols,
+        rgbas,
+        glyph,
+        tempering,
+        recorders,
+    }
+}
+
+fn update(_app: &App, _model: &mut Model, _update: Update) {
+    _model.tempering.sweep();
+    for (recorder, lattice) in _model.recorders.iter_mut().zip(_model.tempering.replicas.iter()) {
+        recorder.record(lattice).unwrap();
+    }
+    println!("acceptance {:?}", _model.tempering.acceptance());
+}
+
+fn view(app: &App, _model: &Model, frame: Frame) {
+    let draw = app.draw();
+    draw.background().color(BLACK);
+    for (k, lattice) in _model.tempering.replicas.iter().enumerate() {
+        // bottom left corner of the panel's lattice
+        let col = k % _model.n_cols;
+        let row = k / _model.n_cols;
+        let px = _model.x0 + col as f32 * _model.panel_width + PANEL_MARGIN;
+        let py = _model.y1 - (row + 1) as f32 * _model.panel_height + PANEL_MARGIN;
+
+        for i in 0..lattice.n_x {
+            for j in 0..lattice.n_y {
+                let val = lattice.a[[i, j]] as usize;
+                let x = px + (i as f32 + 0.5) * _model.w_x;
+                let y = py + (j as f32 + 0.5) * _model.w_y;
+                draw_glyph(&draw, _model.glyph.as_ref(), &_model.rgbas, STROKE_WEIGHT, val, pt2(x, y), vec2(_model.w_x, _model.w_y));
+            }
+        }
+
+        // label the panel with its beta, in the margin above the lattice
+        let label = format!("beta {:.3}", lattice.beta.mean());
+        draw.text(&label)
+            .color(WHITE)
+            .font_size(10)
+            .left_justify()
+            .w_h(_model.panel_width - 2.0 * PANEL_MARGIN, PANEL_MARGIN)
+            .x_y(px + 0.5 * (_model.panel_width - 2.0 * PANEL_MARGIN), py + lattice.n_y as f32 * _model.w_y + 0.5 * PANEL_MARGIN);
+    }
+    draw.to_frame(app, &frame).unwrap();
+
+    // Capture the frame!
+    let file_path = captured_frame_path(app, &frame);
+    app.main_window().capture_frame(file_path);
+}
+
+fn captured_frame_path(app: &App, frame: &Frame) -> std::path::PathBuf {
+    // Create a path that we want to save this frame to.
+    app.project_path()
+        .expect("failed to locate `project_path`")
+        // Capture all frames to a directory called `/<path_to_nannou>/nannou/simple_capture`.
+        .join(app.exe_name().unwrap())
+        // Name each file after the number of the frame.
+        .join(format!("{:03}", frame.nth()))
+        // The extension will be PNG. We also support tiff, bmp, gif, jpeg, webp and some others.
+        .with_extension("png")
+}
+
+fn observables_path(app: &App, name: &str) -> std::path::PathBuf {
+    // Write the observables next to the captured frames.
+    app.project_path()
+        .expect("failed to locate `project_path`")
+        .join(app.exe_name().unwrap())
+        .join(name)
+        .with_extension("csv")
+}
+
+fn main() {
+    nannou::app(model).update(update).run();
+}
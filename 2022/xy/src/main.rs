@@ -0,0 +1,197 @@
+use lattice::args::arg;
+use lattice::palette;
+use lattice::{Beta, Boundary, Recorder, Schedule, Shape, XyModel};
+use nannou::image;
+use nannou::prelude::*;
+use std::path::PathBuf;
+
+const SEED: u64 = 12345;
+const WINDOW_WIDTH: f32 = 600.0;
+const WINDOW_HEIGHT: f32 = 600.0;
+const SQUARE_WIDTH: f32 = 500.0;
+const SQUARE_HEIGHT: f32 = 500.0;
+const SPIN_WIDTH_X: f32 = 5.0;
+const SPIN_WIDTH_Y: f32 = 5.0;
+const BETA_KT: f32 = 1.12; // Kosterlitz-Thouless point of the XY model
+const BETA_START: f32 = 0.5 * BETA_KT;
+const BETA_END: f32 = 2.0 * BETA_KT;
+const N_STEPS: usize = 200;
+const SHAPE: Shape = Shape::Linear;
+const N_CLOCK: usize = 0;
+const BOUNDARY: Boundary = Boundary::Periodic;
+const VORTEX_RGBA: [u8; 4] = [u8::MAX, u8::MAX, u8::MAX, u8::MAX];
+const ANTIVORTEX_RGBA: [u8; 4] = [0, 0, 0, u8::MAX];
+
+struct Model {
+    _window: window::Id,
+    w_x: f32,
+    w_y: f32,
+    x0: f32,
+    x1: f32,
+    y0: f32,
+    y1: f32,
+    lattice: XyModel,
+    recorder: Recorder,
+    schedule: Schedule,
+    step: usize,
+    texture: wgpu::Texture,
+}
+
+fn model(app: &App) -> Model {
+    let _window = app
+        .new_window()
+        .size(WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32)
+        .view(view)
+        .build()
+        .unwrap();
+    let window = app.main_window();
+
+    let offset_x = (WINDOW_WIDTH - SQUARE_WIDTH) / 2.0;
+    let offset_y = (WINDOW_HEIGHT - SQUARE_HEIGHT) / 2.0;
+    let x0: f32 = offset_x - 0.5*WINDOW_WIDTH;
+    let y0: f32 = offset_y - 0.5*WINDOW_HEIGHT;
+    let x1: f32 = SQUARE_WIDTH + offset_x - 0.5*WINDOW_WIDTH;
+    let y1: f32 = SQUARE_HEIGHT + offset_y - 0.5*WINDOW_HEIGHT;
+
+    let w_x: f32 = SPIN_WIDTH_X;
+    let w_y: f32 = SPIN_WIDTH_Y;
+    let n_x: usize = SQUARE_WIDTH as usize / w_x as usize;
+    let n_y: usize = SQUARE_HEIGHT as usize / w_y as usize;
+
+    println!("w_x {}, w_y {}, n_x {}, n_y {}", w_x, w_y, n_x, n_y);
+    // a loop out to the far end and back, or keyframes from --schedule path
+    let schedule: Schedule = match arg::<PathBuf>("schedule") {
+        Some(path) => Schedule::load(path).unwrap(),
+        None => Schedule::cycle(arg("shape").unwrap_or(SHAPE), BETA_START, BETA_END, 2 * N_STEPS),
+    };
+    println!("schedule {}", schedule);
+    let beta = schedule.at(0);
+
+    // clock spins with --clock q, continuous angles otherwise
+    let n_clock: usize = arg("clock").unwrap_or(N_CLOCK);
+    let mut lattice = XyModel::clock(n_x, n_y, n_clock, Beta::Global(beta), SEED);
+    lattice.randomize();
+    lattice.set_boundary(arg("boundary").unwrap_or(BOUNDARY));
+    lattice.couplings = arg("couplings").unwrap_or(lattice.couplings);
+    println!("clock {}, boundary {}, couplings {}", lattice.n_clock, lattice.boundary, lattice.couplings);
+    let recorder = Recorder::create(observables_path(app)).unwrap();
+    let step: usize = 0;
+
+    let texture = wgpu::TextureBuilder::new()
+        .size([WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32])
+        .format(wgpu::TextureFormat::Rgba8Unorm)
+        .usage(wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING)
+        .build(window.device());
+
+    Model {
+        _window,
+        w_x,
+        w_y,
+        x0,
+        x1,
+        y0,
+        y1,
+        lattice,
+        recorder,
+        schedule,
+        step,
+        texture,
+    }
+}
+
+fn update(_app: &App, _model: &mut Model, _update: Update) {
+    _model.lattice.sweep();
+    _model.recorder.record(&_model.lattice).unwrap();
+
+    // next step's beta
+    let beta = _model.schedule.at(_model.step + 1);
+    _model.lattice.beta = Beta::Global(beta);
+
+    // step
+    _model.step += 1
+}
+
+fn get_rgba(pixel_x: usize, pixel_y: usize, _model: &Model) -> [u8; 4] {
+    let x = pixel_x as f32 - 0.5 * WINDOW_WIDTH;
+    let y = pixel_y as f32 - 0.5 * WINDOW_HEIGHT;
+    if _model.x0 <= x && x < _model.x1 && _model.y0 <= y && y < _model.y1 {
+        let i: usize = ((x - _model.x0) / _model.w_x) as usize;
+        let j: usize = ((y - _model.y0) / _model.w_y) as usize;
+        palette::hue(_model.lattice.theta[[i, j]])
+    } else {
+        let bg: [u8; 4] = [0, 0, 0, u8::MAX];
+        bg
+    }
+}
+
+fn get_color(values: [u8; 4]) -> Rgba<u8> {
+    let color = rgba(values[0], values[1], values[2], values[3]);
+    color
+}
+
+fn view(app: &App, _model: &Model, frame: Frame) {
+    frame.clear(BLACK);
+
+    let image = image::ImageBuffer::from_fn(WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32, |i, j| {
+        let rgba = get_rgba(i as usize, j as usize, _model);
+        nannou::image::Rgba(rgba)
+    });
+
+    let flat_samples = image.as_flat_samples();
+    _model.texture.upload_data(
+        app.main_window().device(),
+        &mut *frame.command_encoder(),
+        &flat_samples.as_slice(),
+    );
+
+    let draw = app.draw();
+    draw.texture(&_model.texture);
+
+    // mark vortex cores at the centres of their plaquettes
+    for i in 0.._model.lattice.n_x {
+        for j in 0.._model.lattice.n_y {
+            let rgba = match _model.lattice.vorticity(i, j) {
+                0 => continue,
+                w if w > 0 => VORTEX_RGBA,
+                _ => ANTIVORTEX_RGBA,
+            };
+            // texture rows run down the window, drawing coordinates run up
+            let x = _model.x0 + (i as f32 + 1.0) * _model.w_x;
+            let y = _model.y0 + (j as f32 + 1.0) * _model.w_y;
+            draw.ellipse()
+                .color(get_color(rgba))
+                .w_h(_model.w_x, _model.w_y)
+                .x_y(x, -y);
+        }
+    }
+    draw.to_frame(app, &frame).unwrap();
+
+    // Capture the frame!
+    let file_path = captured_frame_path(app, &frame);
+    app.main_window().capture_frame(file_path);
+}
+
+fn captured_frame_path(app: &App, frame: &Frame) -> std::path::PathBuf {
+    // Create a path that we want to save this frame to.
+    app.project_path()
+        .expect("failed to locate `project_path`")
+        // Capture all frames to a directory called `/<path_to_nannou>/nannou/simple_capture`.
+        .join(app.exe_name().unwrap())
+        // Name each file after the number of the frame.
+        .join(format!("{:03}", frame.nth()))
+        // The extension will be PNG. We also support tiff, bmp, gif, jpeg, webp and some others.
+        .with_extension("png")
+}
+
+fn observables_path(app: &App) -> std::path::PathBuf {
+    // Write the observables next to the captured frames.
+    app.project_path()
+        .expect("failed to locate `project_path`")
+        .join(app.exe_name().unwrap())
+        .join("observables")
+        .with_extension("csv")
+}
+
+fn main() {
+    nannou::app(model).update(update).run();
+}